@@ -1,2 +1,3 @@
+#[allow(clippy::module_inception)]
 mod assets;
 pub use assets::*;
@@ -17,7 +17,8 @@ impl Plugin for AssetPlugin {
 
 // Asset loading
 
-#[derive(Resource)]
+// Default handles are empty, which is what the headless simulation uses
+#[derive(Resource, Default)]
 pub struct GameAssets {
   // Fonts
   pub font: Handle<Font>,
@@ -167,6 +168,15 @@ impl GameAssets {
       _ => self.grass_tile.clone(),
     }
   }
+
+  // Textures of the map's tilemap, indexed by `Tile::texture_index`
+  pub fn tile_textures(&self) -> Vec<Handle<Image>> {
+    vec![
+      self.grass_tile.clone(),
+      self.water_tile.clone(),
+      self.path_tile.clone(),
+    ]
+  }
 }
 
 fn load_assets(
@@ -327,11 +337,7 @@ fn animate_enemy_sprite(
   for (indices, mut timer, mut sprite, movement) in &mut query {
     // Change direction based on where enemy is heading
     if movement.direction.x != 0. {
-      if movement.direction.x < 0. {
-        sprite.flip_x = true;
-      } else {
-        sprite.flip_x = false;
-      }
+      sprite.flip_x = movement.direction.x < 0.;
     }
 
     // Animate sprite
@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 mod enemy;
 pub use enemy::*;
 
@@ -6,3 +7,18 @@ pub use enemy_type::*;
 
 mod wave;
 pub use wave::*;
+
+mod status_effect;
+pub use status_effect::*;
+
+mod boss;
+pub use boss::*;
+
+mod health_bar;
+pub use health_bar::*;
+
+mod enemy_traits;
+pub use enemy_traits::*;
+
+mod enemy_grid;
+pub use enemy_grid::*;
@@ -0,0 +1,241 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::time::Duration;
+
+use crate::assets::*;
+use crate::enemy::*;
+use crate::movement::*;
+use crate::{GameData, Map, SimulationSet};
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+  fn build(&self, app: &mut App) {
+    app.add_systems(
+      (init_bosses, update_boss_phases)
+        .chain()
+        .after(tick_enemy_traits)
+        .in_set(SimulationSet::Cleanup)
+        .in_schedule(CoreSchedule::FixedUpdate),
+    );
+  }
+}
+
+// Boss of an enemy type - stats.enemy_types.ron
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BossStats {
+  // Shown on the boss health bar
+  pub name: String,
+  // Sprite size compared to the other enemies
+  pub scale: f32,
+  // Ordered from the highest health threshold to the lowest
+  #[serde(default)]
+  pub phases: Vec<BossPhase>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BossPhase {
+  // Part of the boss' health, from 0 to 1, at or below which the phase starts
+  pub health: f32,
+  pub action: BossAction,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum BossAction {
+  // Multiplies movement speed
+  SpeedUp(f32),
+  // Enemies spawned where the boss is
+  SpawnMinions(EnemyType, usize),
+  // Seconds without taking damage
+  Immune(f32),
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Boss {
+  pub name: String,
+  pub max_health: i32,
+  // First phase that hasn't started yet
+  pub next_phase: usize,
+}
+
+// Enemy that takes no damage until the timer finishes
+#[derive(Component, Debug, Clone)]
+pub struct Immune(pub Timer);
+
+impl Immune {
+  // Whether immunity ran out this tick
+  pub fn tick(&mut self, delta: Duration) -> bool {
+    self.0.tick(delta).just_finished()
+  }
+}
+
+// Phases that start at this health, after the ones started before `next_phase`.
+// Phases only start once, healing back above a threshold doesn't start it again
+pub fn new_phases(
+  phases: &[BossPhase],
+  max_health: i32,
+  health: i32,
+  next_phase: usize,
+) -> Range<usize> {
+  let reached = phases
+    .iter()
+    .take_while(|phase| health as f32 <= phase.health * max_health as f32)
+    .count();
+
+  next_phase..reached.max(next_phase)
+}
+
+// Phases are worked out from the health, so loaded games continue where they were
+fn init_bosses(
+  mut commands: Commands,
+  mut enemies: Query<(Entity, &Enemy, &EnemyType, &mut Transform), Added<EnemyType>>,
+  game_data: Res<GameData>,
+  enemy_stats: Res<Assets<EnemyTypeStats>>,
+) {
+  let Some(enemy_stats) = enemy_stats.get(&game_data.enemy_type_stats)
+    else { return; };
+
+  for (entity, enemy, enemy_type, mut transform) in &mut enemies {
+    let Some(boss_stats) = enemy_stats.bosses.get(enemy_type)
+      else { continue; };
+
+    let max_health = enemy_stats.enemy[enemy_type].enemy.health;
+    let next_phase = new_phases(&boss_stats.phases, max_health, enemy.health, 0).end;
+
+    transform.scale = Vec3::splat(boss_stats.scale);
+    commands.entity(entity).insert(Boss {
+      name: boss_stats.name.clone(),
+      max_health,
+      next_phase,
+    });
+  }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn update_boss_phases(
+  mut commands: Commands,
+  mut bosses: Query<(
+    Entity,
+    &mut Boss,
+    &Enemy,
+    &EnemyType,
+    &mut Movement,
+    &Path,
+    &Transform,
+    Option<&mut Immune>,
+    Option<&FromWave>,
+  )>,
+  assets: Res<GameAssets>,
+  game_data: Res<GameData>,
+  map: Res<Assets<Map>>,
+  enemy_stats: Res<Assets<EnemyTypeStats>>,
+  fixed_time: Res<FixedTime>,
+) {
+  let Some(map) = map.get(&game_data.map)
+    else { return; };
+  let Some(enemy_stats) = enemy_stats.get(&game_data.enemy_type_stats)
+    else { return; };
+
+  for (entity, mut boss, enemy, enemy_type, mut movement, path, transform, immune, from_wave) in
+    &mut bosses
+  {
+    if let Some(mut immune) = immune {
+      if immune.tick(fixed_time.period) {
+        commands.entity(entity).remove::<Immune>();
+      }
+    }
+
+    // Dead bosses are despawned, they don't start another phase
+    if enemy.health <= 0 {
+      continue;
+    }
+    let Some(boss_stats) = enemy_stats.bosses.get(enemy_type)
+      else { continue; };
+
+    let phases = new_phases(&boss_stats.phases, boss.max_health, enemy.health, boss.next_phase);
+    boss.next_phase = phases.end;
+    for index in phases {
+      let phase = &boss_stats.phases[index];
+      info!("{} PHASE {}", boss.name.to_uppercase(), index + 1);
+
+      match &phase.action {
+        BossAction::SpeedUp(multiplier) => movement.speed *= multiplier,
+        BossAction::SpawnMinions(minion_type, count) => {
+          for _ in 0..*count {
+            let mut minion = minion_type.get_enemy(map, path.clone(), enemy_stats);
+            // Same place on the path as the boss
+            minion.movement.distance_travelled = movement.distance_travelled;
+
+            let mut minion = commands.spawn(minion);
+            minion.insert(minion_type.get_sprite_sheet_bundle(&assets, transform.translation));
+            if let Some(from_wave) = from_wave {
+              minion.insert(*from_wave);
+            }
+          }
+        }
+        BossAction::Immune(seconds) => {
+          commands
+            .entity(entity)
+            .insert(Immune(Timer::from_seconds(*seconds, TimerMode::Once)));
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn phases() -> Vec<BossPhase> {
+    [0.75, 0.5, 0.25]
+      .map(|health| BossPhase {
+        health,
+        action: BossAction::SpeedUp(1.5),
+      })
+      .to_vec()
+  }
+
+  #[test]
+  fn phases_start_at_their_threshold() {
+    let phases = phases();
+    let reached = |health| new_phases(&phases, 100, health, 0).end;
+
+    assert_eq!(reached(100), 0);
+    assert_eq!(reached(76), 0);
+    assert_eq!(reached(75), 1);
+    assert_eq!(reached(50), 2);
+    assert_eq!(reached(26), 2);
+    assert_eq!(reached(1), 3);
+    assert_eq!(reached(-5), 3);
+  }
+
+  #[test]
+  fn every_phase_starts_once() {
+    let phases = phases();
+    let mut next_phase = 0;
+    let mut started = vec![];
+
+    // Healing back above a threshold, then taking one big hit past the last two
+    for health in [100, 80, 70, 70, 60, 80, 70, 40, 10, 0] {
+      let new = new_phases(&phases, 100, health, next_phase);
+      next_phase = new.end;
+      started.push(new.collect::<Vec<_>>());
+    }
+
+    assert_eq!(
+      started,
+      vec![vec![], vec![], vec![0], vec![], vec![], vec![], vec![], vec![1], vec![2], vec![]]
+    );
+  }
+
+  #[test]
+  fn immunity_runs_out_once() {
+    let mut immune = Immune(Timer::from_seconds(1., TimerMode::Once));
+
+    assert!(!immune.tick(Duration::from_millis(600)));
+    assert!(immune.tick(Duration::from_millis(600)));
+    assert!(!immune.tick(Duration::from_millis(600)));
+  }
+}
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 use crate::assets::*;
 use crate::enemy::*;
 use crate::movement::*;
-use crate::{GameState, Map};
+use crate::{GameData, Map, SimulationSet};
 
 pub struct EnemyPlugin;
 
@@ -14,12 +14,31 @@ impl Plugin for EnemyPlugin {
       .register_type::<Enemy>()
       .register_type::<Path>()
       .add_event::<EnemyDeathEvent>()
+      .add_event::<EnemyLeakedEvent>()
       //.add_startup_system(load_enemy_type_stats)
-      .add_system(despawn_enemy_on_death.in_set(OnUpdate(GameState::Gameplay)));
+      .add_system(
+        despawn_enemy_on_death
+          .after(update_boss_phases)
+          .in_set(SimulationSet::Cleanup)
+          .in_schedule(CoreSchedule::FixedUpdate),
+      );
   }
 }
 
-pub struct EnemyDeathEvent;
+pub struct EnemyDeathEvent {
+  pub enemy_type: EnemyType,
+  pub position: Vec3,
+  // Tower that hit the enemy last, if any
+  pub tower: Option<Entity>,
+  pub bounty: usize,
+}
+
+// Enemy that reached the end of its path
+pub struct EnemyLeakedEvent {
+  pub enemy_type: EnemyType,
+  // Base health lost
+  pub damage: i32,
+}
 
 #[derive(Bundle, Debug, Serialize, Deserialize, Clone)]
 pub struct EnemyBundle {
@@ -29,6 +48,12 @@ pub struct EnemyBundle {
   pub animation_indices: AnimationIndices,
   pub animation_timer: AnimationTimer,
   pub path: Path,
+  #[serde(default)]
+  pub status_effects: StatusEffects,
+  #[serde(default)]
+  pub traits: EnemyTraits,
+  #[serde(skip)]
+  pub last_hit_by: LastHitBy,
   pub name: Name,
 }
 
@@ -43,7 +68,10 @@ impl Default for EnemyBundle {
       },
       animation_indices: AnimationIndices { first: 0, last: 9 },
       animation_timer: AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
-      path: Path { index: 0 },
+      path: Path::default(),
+      status_effects: StatusEffects::default(),
+      traits: EnemyTraits::default(),
+      last_hit_by: LastHitBy::default(),
       name: Name::new("GreenEnemy"),
     }
   }
@@ -53,17 +81,41 @@ impl Default for EnemyBundle {
 #[reflect(Component)]
 pub struct Enemy {
   pub health: i32,
+  // Damage below a whole point left over from multiplied hits
+  #[serde(default)]
+  pub damage_progress: f32,
 }
 
+// Tower whose bullet or damage over time hit the enemy last
+#[derive(Component, Default, Clone, Debug)]
+pub struct LastHitBy(pub Option<Entity>);
+
 #[derive(Reflect, Component, Default, Clone, Serialize, Debug, Deserialize)]
 #[reflect(Component)]
 pub struct Path {
+  // Next checkpoint
   pub index: usize,
+  // Index of the map path the enemy follows
+  #[serde(default)]
+  pub route: usize,
 }
 
 impl Enemy {
   pub fn new(health: i32) -> Self {
-    Self { health }
+    Self {
+      health,
+      damage_progress: 0.,
+    }
+  }
+
+  // Damage after resistances, weaknesses and curses. Fractions add up over hits instead
+  // of being rounded away, so a 0.5 resistance halves even 1 damage hits
+  pub fn scale_damage(&mut self, damage: u32, multiplier: f32) -> u32 {
+    self.damage_progress += damage as f32 * multiplier.max(0.);
+    let whole = self.damage_progress.floor();
+    self.damage_progress -= whole;
+
+    whole as u32
   }
 }
 
@@ -75,22 +127,169 @@ pub fn spawn_enemy(
   position: Vec3,
   path: Path,
   enemy_stats: &EnemyTypeStats,
-) {
+) -> Entity {
   commands
     .spawn(enemy_type.get_enemy(map_path, path, enemy_stats))
-    .insert(enemy_type.get_sprite_sheet_bundle(assets, position));
+    .insert(enemy_type.get_sprite_sheet_bundle(assets, position))
+    .id()
+}
+
+// Damage each child takes from the parent's overkill. They take it in turn, up to their health,
+// and the last one takes whatever is left
+pub fn split_overkill(mut overkill: i32, child_health: &[i32]) -> Vec<i32> {
+  child_health
+    .iter()
+    .enumerate()
+    .map(|(index, &health)| {
+      let damage = if index + 1 == child_health.len() {
+        overkill
+      } else {
+        overkill.min(health)
+      };
+      overkill -= damage;
+      damage
+    })
+    .collect()
+}
+
+// Children of an enemy that died with `health` left, at the same place on its path
+pub fn release_children(
+  enemy_stats: &EnemyTypeStats,
+  map: &Map,
+  enemy_type: EnemyType,
+  path: &Path,
+  movement: &Movement,
+  health: i32,
+) -> Vec<EnemyBundle> {
+  let Some(children) = enemy_stats.children.get(&enemy_type)
+    else { return vec![]; };
+
+  let mut children = children
+    .iter()
+    .flat_map(|(child_type, count)| std::iter::repeat_n(*child_type, *count))
+    .map(|child_type| {
+      let mut child = child_type.get_enemy(map, path.clone(), enemy_stats);
+      // Same place on the path, so targeting order stays the same
+      child.movement.distance_travelled = movement.distance_travelled;
+      child
+    })
+    .collect::<Vec<_>>();
+
+  let overkill = if enemy_stats.overkill_carries_over { -health } else { 0 };
+  let child_health = children.iter().map(|child| child.enemy.health).collect::<Vec<_>>();
+  for (child, damage) in children.iter_mut().zip(split_overkill(overkill, &child_health)) {
+    child.enemy.health -= damage;
+  }
+
+  children
 }
 
-// !!! Spawn weaker enemy?
-fn despawn_enemy_on_death(
+// Dead enemies release their children where they died
+#[allow(clippy::type_complexity)]
+pub fn despawn_enemy_on_death(
   mut commands: Commands,
-  enemies: Query<(Entity, &mut Enemy)>,
+  enemies: Query<(
+    Entity,
+    &Enemy,
+    &EnemyType,
+    &Movement,
+    &Transform,
+    &Path,
+    &LastHitBy,
+    Option<&FromWave>,
+  )>,
   mut death_event_writer: EventWriter<EnemyDeathEvent>,
+  assets: Res<GameAssets>,
+  game_data: Res<GameData>,
+  map: Res<Assets<Map>>,
+  enemy_stats: Res<Assets<EnemyTypeStats>>,
 ) {
-  for (entity, enemy) in &enemies {
+  let Some(map) = map.get(&game_data.map)
+    else { return; };
+  let Some(enemy_stats) = enemy_stats.get(&game_data.enemy_type_stats)
+    else { return; };
+
+  for (entity, enemy, enemy_type, movement, transform, path, last_hit_by, from_wave) in &enemies {
     if enemy.health <= 0 {
-      death_event_writer.send(EnemyDeathEvent);
+      death_event_writer.send(EnemyDeathEvent {
+        enemy_type: *enemy_type,
+        position: transform.translation,
+        tower: last_hit_by.0,
+        bounty: enemy_stats.bounty(*enemy_type),
+      });
       commands.entity(entity).despawn_recursive();
+
+      for child in release_children(enemy_stats, map, *enemy_type, path, movement, enemy.health) {
+        let sprite = child.enemy_type.get_sprite_sheet_bundle(&assets, transform.translation);
+        let mut child = commands.spawn(child);
+        child.insert(sprite);
+        if let Some(from_wave) = from_wave {
+          child.insert(*from_wave);
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn stats(overkill_carries_over: bool) -> EnemyTypeStats {
+    let mut stats: EnemyTypeStats =
+      ron::from_str(include_str!("../../assets/data/stats.enemy_types.ron")).unwrap();
+    stats.overkill_carries_over = overkill_carries_over;
+    stats
+  }
+
+  fn map() -> Map {
+    ron::from_str("(width: 0, height: 0, tiles: [], tile_size: 80)").unwrap()
+  }
+
+  fn movement(distance_travelled: f32) -> Movement {
+    Movement {
+      distance_travelled,
+      ..default()
     }
   }
+
+  #[test]
+  fn overkill_fills_children_in_turn() {
+    assert_eq!(split_overkill(5, &[3, 3, 3]), vec![3, 2, 0]);
+    assert_eq!(split_overkill(0, &[3, 3]), vec![0, 0]);
+    assert_eq!(split_overkill(2, &[]), Vec::<i32>::new());
+  }
+
+  #[test]
+  fn last_child_takes_the_rest() {
+    assert_eq!(split_overkill(10, &[3, 3, 3]), vec![3, 3, 4]);
+    assert_eq!(split_overkill(7, &[4]), vec![7]);
+  }
+
+  #[test]
+  fn children_take_the_overkill() {
+    // Red releases a Purple with 7 health
+    let path = Path { index: 2, route: 0 };
+    let children = release_children(&stats(true), &map(), EnemyType::Red, &path, &movement(123.), -3);
+
+    assert_eq!(children.len(), 1);
+    assert_eq!(children[0].enemy_type, EnemyType::Purple);
+    assert_eq!(children[0].enemy.health, 4);
+    assert_eq!(children[0].movement.distance_travelled, 123.);
+    assert_eq!(children[0].path.index, 2);
+
+    // Overkill beyond the child's health leaves it dead on release
+    let children = release_children(&stats(true), &map(), EnemyType::Red, &path, &movement(0.), -9);
+    assert_eq!(children[0].enemy.health, -2);
+  }
+
+  #[test]
+  fn overkill_can_be_turned_off() {
+    let path = Path { index: 0, route: 0 };
+    let children = release_children(&stats(false), &map(), EnemyType::Red, &path, &movement(50.), -3);
+
+    assert_eq!(children[0].enemy.health, 7);
+    assert_eq!(children[0].movement.distance_travelled, 50.);
+    assert!(release_children(&stats(false), &map(), EnemyType::Green, &path, &movement(0.), -3).is_empty());
+  }
 }
@@ -0,0 +1,392 @@
+use bevy::prelude::*;
+
+use crate::enemy::*;
+use crate::SimulationSet;
+
+// Collision size of an unscaled enemy
+pub const ENEMY_SIZE: Vec2 = Vec2::new(30., 30.);
+// About two enemies wide, so most range and collision queries only look at a few cells
+const CELL_SIZE: f32 = 64.;
+
+pub struct EnemyGridPlugin;
+
+impl Plugin for EnemyGridPlugin {
+  fn build(&self, app: &mut App) {
+    app.init_resource::<EnemyGrid>().add_system(
+      rebuild_enemy_grid
+        .in_set(SimulationSet::Indexing)
+        .in_schedule(CoreSchedule::FixedUpdate),
+    );
+  }
+}
+
+// Uniform grid of enemy positions, rebuilt every tick after enemies move.
+// Enemies are sorted by cell, so every row of cells in a query is one slice.
+// Queries return the enemies in the cells they touch, callers still check the exact distance
+#[derive(Resource)]
+pub struct EnemyGrid {
+  cell_size: f32,
+  // Cell size of the last rebuild, larger than `cell_size` when enemies are very spread out
+  current_cell_size: f32,
+  // First cell and number of cells, covering every enemy
+  origin: IVec2,
+  size: IVec2,
+  // Enemies of cell `i` are `entities[cell_starts[i]..cell_starts[i + 1]]`
+  cell_starts: Vec<usize>,
+  entities: Vec<Entity>,
+  // Largest half size of an enemy, so overlap queries reach enemies centered in other cells
+  max_half_size: f32,
+  // Reused between rebuilds
+  positions: Vec<(Entity, Vec2)>,
+}
+
+// Limits the memory used when enemies are far apart
+const MAX_CELLS: i32 = 1 << 16;
+
+impl Default for EnemyGrid {
+  fn default() -> Self {
+    Self::new(CELL_SIZE)
+  }
+}
+
+impl EnemyGrid {
+  pub fn new(cell_size: f32) -> Self {
+    Self {
+      cell_size,
+      current_cell_size: cell_size,
+      origin: IVec2::ZERO,
+      size: IVec2::ZERO,
+      cell_starts: vec![0],
+      entities: vec![],
+      max_half_size: 0.,
+      positions: vec![],
+    }
+  }
+
+  // Replace the grid's enemies with (entity, position, half size of its collision box)
+  pub fn rebuild(&mut self, enemies: impl Iterator<Item = (Entity, Vec3, f32)>) {
+    self.positions.clear();
+    self.max_half_size = 0.;
+    let (mut min, mut max) = (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN));
+    for (entity, position, half_size) in enemies {
+      let position = position.truncate();
+      self.positions.push((entity, position));
+      self.max_half_size = self.max_half_size.max(half_size);
+      min = min.min(position);
+      max = max.max(position);
+    }
+
+    self.entities.clear();
+    self.cell_starts.clear();
+    if self.positions.is_empty() {
+      self.size = IVec2::ZERO;
+      self.cell_starts.push(0);
+      return;
+    }
+
+    self.current_cell_size = self.cell_size;
+    loop {
+      self.origin = self.cell(min);
+      self.size = self.cell(max) - self.origin + 1;
+      if self.size.x.saturating_mul(self.size.y) <= MAX_CELLS {
+        break;
+      }
+      self.current_cell_size *= 2.;
+    }
+
+    // Count the enemies of every cell, then turn the counts into where each cell starts
+    self.cell_starts.resize((self.size.x * self.size.y) as usize + 1, 0);
+    for index in 0..self.positions.len() {
+      let cell = self.cell_index(self.cell(self.positions[index].1));
+      self.cell_starts[cell + 1] += 1;
+    }
+    for cell in 1..self.cell_starts.len() {
+      self.cell_starts[cell] += self.cell_starts[cell - 1];
+    }
+
+    // Fill every cell from its start, `cell_starts` temporarily holds the next free slot
+    self.entities.resize(self.positions.len(), Entity::PLACEHOLDER);
+    for index in 0..self.positions.len() {
+      let (entity, position) = self.positions[index];
+      let cell = self.cell_index(self.cell(position));
+      self.entities[self.cell_starts[cell]] = entity;
+      self.cell_starts[cell] += 1;
+    }
+    // Every cell now starts where the next one started, shift them back
+    for cell in (1..self.cell_starts.len()).rev() {
+      self.cell_starts[cell] = self.cell_starts[cell - 1];
+    }
+    self.cell_starts[0] = 0;
+  }
+
+  // Enemies whose center may be within the radius
+  pub fn in_radius(&self, center: Vec3, radius: f32) -> impl Iterator<Item = Entity> + '_ {
+    self.in_rect(center.truncate() - radius, center.truncate() + radius)
+  }
+
+  // Enemies whose collision box may overlap a box of the given half size
+  pub fn overlapping(&self, center: Vec3, half_size: Vec2) -> impl Iterator<Item = Entity> + '_ {
+    let half_size = half_size + self.max_half_size;
+    self.in_rect(center.truncate() - half_size, center.truncate() + half_size)
+  }
+
+  fn in_rect(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = Entity> + '_ {
+    // Outside the grid there are no enemies
+    let min = (self.cell(min) - self.origin).max(IVec2::ZERO);
+    let max = (self.cell(max) - self.origin).min(self.size - 1);
+    // No columns means no rows either
+    let rows = min.y..=if min.x <= max.x { max.y } else { min.y - 1 };
+
+    rows.flat_map(move |y| {
+      let row = (y * self.size.x) as usize;
+      let (first, last) = (row + min.x as usize, row + max.x as usize);
+      &self.entities[self.cell_starts[first]..self.cell_starts[last + 1]]
+    })
+    .copied()
+  }
+
+  fn cell(&self, position: Vec2) -> IVec2 {
+    (position / self.current_cell_size).floor().as_ivec2()
+  }
+
+  fn cell_index(&self, cell: IVec2) -> usize {
+    let cell = cell - self.origin;
+    (cell.y * self.size.x + cell.x) as usize
+  }
+}
+
+fn rebuild_enemy_grid(mut grid: ResMut<EnemyGrid>, enemies: Query<(Entity, &Transform), With<Enemy>>) {
+  grid.rebuild(enemies.iter().map(|(entity, transform)| {
+    // Bosses are scaled up
+    let half_size = (ENEMY_SIZE * transform.scale.truncate()).max_element() / 2.;
+    (entity, transform.translation, half_size)
+  }));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::rngs::StdRng;
+  use rand::{Rng, SeedableRng};
+  use std::collections::HashSet;
+  use std::time::Instant;
+
+  const HALF_SIZE: f32 = 15.;
+
+  // Enemy `i` is at `positions[i]`
+  fn rebuild(grid: &mut EnemyGrid, positions: &[Vec2]) {
+    grid.rebuild(
+      positions
+        .iter()
+        .enumerate()
+        .map(|(index, position)| (Entity::from_raw(index as u32), position.extend(0.), HALF_SIZE)),
+    );
+  }
+
+  fn grid(cell_size: f32, positions: &[Vec2]) -> EnemyGrid {
+    let mut grid = EnemyGrid::new(cell_size);
+    rebuild(&mut grid, positions);
+    grid
+  }
+
+  fn random_positions(rng: &mut StdRng, count: usize, min: Vec2, max: Vec2) -> Vec<Vec2> {
+    (0..count)
+      .map(|_| Vec2::new(rng.gen_range(min.x..max.x), rng.gen_range(min.y..max.y)))
+      .collect()
+  }
+
+  // Every candidate once, and at least every enemy the exact check accepts
+  fn assert_covers(
+    found: impl Iterator<Item = Entity>,
+    positions: &[Vec2],
+    exact: impl Fn(Vec2) -> bool,
+  ) {
+    let found = found.collect::<Vec<_>>();
+    let unique = found.iter().copied().collect::<HashSet<_>>();
+    assert_eq!(found.len(), unique.len(), "enemy returned twice");
+    for (index, position) in positions.iter().enumerate() {
+      if exact(*position) {
+        assert!(unique.contains(&Entity::from_raw(index as u32)), "missed enemy at {position}");
+      }
+    }
+  }
+
+  fn check_queries(grid: &EnemyGrid, positions: &[Vec2], queries: &[Vec2]) {
+    for &center in queries {
+      for radius in [0., 10., 64., 175., 500.] {
+        assert_covers(grid.in_radius(center.extend(0.), radius), positions, |position| {
+          position.distance(center) <= radius
+        });
+      }
+      for half_size in [Vec2::ZERO, Vec2::new(20., 11.), Vec2::splat(100.)] {
+        assert_covers(grid.overlapping(center.extend(0.), half_size), positions, |position| {
+          let distance = (position - center).abs();
+          distance.x < half_size.x + HALF_SIZE && distance.y < half_size.y + HALF_SIZE
+        });
+      }
+    }
+  }
+
+  #[test]
+  fn empty_grid_finds_nothing() {
+    let mut grid = grid(CELL_SIZE, &[]);
+    assert_eq!(grid.in_radius(Vec3::ZERO, 1000.).count(), 0);
+    assert_eq!(grid.overlapping(Vec3::ZERO, Vec2::splat(1000.)).count(), 0);
+
+    // Emptied after holding enemies
+    rebuild(&mut grid, &[Vec2::ZERO]);
+    assert_eq!(grid.in_radius(Vec3::ZERO, 10.).count(), 1);
+    rebuild(&mut grid, &[]);
+    assert_eq!(grid.in_radius(Vec3::ZERO, 1000.).count(), 0);
+  }
+
+  #[test]
+  fn queries_match_a_scan() {
+    let mut rng = StdRng::seed_from_u64(1);
+    // Negative positions too, cells are floored
+    let positions =
+      random_positions(&mut rng, 500, Vec2::new(-400., -300.), Vec2::new(1600., 900.));
+    let grid = grid(CELL_SIZE, &positions);
+
+    let mut queries = random_positions(&mut rng, 50, Vec2::new(-600., -500.), Vec2::new(1800., 1100.));
+    // On enemies and exactly on cell corners
+    queries.extend(positions.iter().take(10));
+    queries.extend([Vec2::ZERO, Vec2::splat(CELL_SIZE), Vec2::new(-CELL_SIZE, 2. * CELL_SIZE)]);
+    check_queries(&grid, &positions, &queries);
+  }
+
+  #[test]
+  fn queries_outside_the_grid() {
+    let positions = [Vec2::new(100., 100.), Vec2::new(200., 150.), Vec2::new(130., 260.)];
+    let grid = grid(CELL_SIZE, &positions);
+
+    // Far away on every side
+    for center in [
+      Vec2::new(-5000., 100.),
+      Vec2::new(5000., 100.),
+      Vec2::new(100., -5000.),
+      Vec2::new(100., 5000.),
+      Vec2::splat(-5000.),
+    ] {
+      assert_eq!(grid.in_radius(center.extend(0.), 100.).count(), 0);
+      assert_eq!(grid.overlapping(center.extend(0.), Vec2::splat(100.)).count(), 0);
+    }
+    // Only partly overlapping the grid, or containing all of it
+    check_queries(
+      &grid,
+      &positions,
+      &[Vec2::new(0., 0.), Vec2::new(300., 100.), Vec2::new(150., 400.), Vec2::new(150., 150.)],
+    );
+    assert_eq!(grid.in_radius(Vec3::new(150., 150., 0.), 5000.).count(), positions.len());
+  }
+
+  #[test]
+  fn spread_out_enemies_use_larger_cells() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut positions = random_positions(&mut rng, 200, Vec2::ZERO, Vec2::new(1600., 900.));
+    // Far enough apart that unscaled cells would go over MAX_CELLS
+    positions.extend([Vec2::new(-100_000., -50_000.), Vec2::new(100_000., 50_000.)]);
+    let mut grid = grid(CELL_SIZE, &positions);
+
+    assert!(grid.current_cell_size > CELL_SIZE);
+    assert!(grid.size.x * grid.size.y <= MAX_CELLS);
+
+    let mut queries = random_positions(&mut rng, 30, Vec2::new(-200., -200.), Vec2::new(1800., 1100.));
+    queries.extend([Vec2::new(-100_000., -50_000.), Vec2::new(99_990., 50_010.)]);
+    check_queries(&grid, &positions, &queries);
+
+    // Back to normal cells once the enemies are close together again
+    let positions = random_positions(&mut rng, 100, Vec2::ZERO, Vec2::new(1600., 900.));
+    rebuild(&mut grid, &positions);
+    assert_eq!(grid.current_cell_size, CELL_SIZE);
+    check_queries(&grid, &positions, &queries);
+  }
+
+  // Compare scanning every enemy with the grid, for tower range checks and bullet collisions:
+  // `cargo test --release grid_benchmark -- --ignored --nocapture`
+  #[test]
+  #[ignore]
+  fn grid_benchmark() {
+    const TOWERS: usize = 50;
+    const BULLETS: usize = 200;
+    const TOWER_RANGE: f32 = 175.;
+    const BULLET_HALF_SIZE: Vec2 = Vec2::new(20., 11.);
+    const TICKS: u32 = 100;
+
+    let enemy_half_size = ENEMY_SIZE / 2.;
+    let overlaps = |a: Vec3, b: Vec3| {
+      let distance = (a - b).truncate().abs();
+      distance.x < BULLET_HALF_SIZE.x + enemy_half_size.x
+        && distance.y < BULLET_HALF_SIZE.y + enemy_half_size.y
+    };
+
+    for enemy_count in [1000, 2000, 5000, 10000] {
+      let mut rng = StdRng::seed_from_u64(42);
+      let mut random_position =
+        || Vec3::new(rng.gen_range(0.0..1600.), rng.gen_range(0.0..900.), 0.);
+
+      let enemies = (0..enemy_count)
+        .map(|index| (Entity::from_raw(index as u32), random_position()))
+        .collect::<Vec<_>>();
+      let towers = (0..TOWERS).map(|_| random_position()).collect::<Vec<_>>();
+      let bullets = (0..BULLETS).map(|_| random_position()).collect::<Vec<_>>();
+      // Looked up by entity index, like a query's `get`
+      let positions = enemies.iter().map(|(_, position)| *position).collect::<Vec<_>>();
+
+      // Every enemy for every tower and bullet
+      let start = Instant::now();
+      let mut scan_hits = 0;
+      for _ in 0..TICKS {
+        for tower in &towers {
+          scan_hits += enemies
+            .iter()
+            .filter(|(_, position)| position.distance(*tower) <= TOWER_RANGE)
+            .count();
+        }
+        for bullet in &bullets {
+          scan_hits += enemies
+            .iter()
+            .find(|(_, position)| overlaps(*bullet, *position))
+            .map_or(0, |_| 1);
+        }
+      }
+      let scan_time = start.elapsed() / TICKS;
+
+      // The grid is rebuilt every tick, the same as in the game
+      let mut grid = EnemyGrid::default();
+      let start = Instant::now();
+      let mut grid_hits = 0;
+      for _ in 0..TICKS {
+        grid.rebuild(
+          enemies
+            .iter()
+            .map(|(entity, position)| (*entity, *position, enemy_half_size.max_element())),
+        );
+        for tower in &towers {
+          grid_hits += grid
+            .in_radius(*tower, TOWER_RANGE)
+            .filter(|entity| positions[entity.index() as usize].distance(*tower) <= TOWER_RANGE)
+            .count();
+        }
+        for bullet in &bullets {
+          grid_hits += grid
+            .overlapping(*bullet, BULLET_HALF_SIZE)
+            .find(|entity| overlaps(*bullet, positions[entity.index() as usize]))
+            .map_or(0, |_| 1);
+        }
+      }
+      let grid_time = start.elapsed() / TICKS;
+
+      assert_eq!(scan_hits, grid_hits);
+      println!(
+        "{} enemies, {} towers, {} bullets: scan {:.3} ms/tick, grid {:.3} ms/tick ({:.1}x)",
+        enemy_count,
+        TOWERS,
+        BULLETS,
+        scan_time.as_secs_f64() * 1000.,
+        grid_time.as_secs_f64() * 1000.,
+        scan_time.as_secs_f64() / grid_time.as_secs_f64().max(f64::EPSILON),
+      );
+    }
+  }
+}
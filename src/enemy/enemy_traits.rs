@@ -0,0 +1,161 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::enemy::*;
+use crate::{GameData, SimulationSet};
+
+pub struct EnemyTraitsPlugin;
+
+impl Plugin for EnemyTraitsPlugin {
+  fn build(&self, app: &mut App) {
+    app.add_system(
+      tick_enemy_traits
+        .in_set(SimulationSet::Cleanup)
+        .in_schedule(CoreSchedule::FixedUpdate),
+    );
+  }
+}
+
+// Optional traits of an enemy type - stats.enemy_types.ron
+#[derive(Component, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EnemyTraits {
+  // Health per second, up to the enemy type's health
+  #[serde(default)]
+  pub regeneration: f32,
+  // Damage absorbed before health is lost, goes down as it's hit
+  #[serde(default)]
+  pub shield: u32,
+  // Only towers with detection can target it
+  #[serde(default)]
+  pub camo: bool,
+  #[serde(default)]
+  pub speed_burst: Option<SpeedBurst>,
+  // Regenerated health that hasn't added up to a whole point yet
+  #[serde(default)]
+  pub regeneration_progress: f32,
+  // Seconds since the enemy spawned, for speed bursts
+  #[serde(default)]
+  pub elapsed: f32,
+}
+
+// Faster for `duration` seconds at the end of every `every` seconds
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpeedBurst {
+  pub every: f32,
+  pub duration: f32,
+  // Multiplies movement speed
+  pub speed: f32,
+}
+
+impl EnemyTraits {
+  // Take damage from the shield first, returns the damage left for health
+  pub fn absorb(&mut self, damage: u32) -> u32 {
+    let absorbed = damage.min(self.shield);
+    self.shield -= absorbed;
+
+    damage - absorbed
+  }
+
+  pub fn speed_multiplier(&self) -> f32 {
+    match &self.speed_burst {
+      Some(burst) if burst.every > 0. && self.elapsed % burst.every >= burst.every - burst.duration => {
+        burst.speed
+      }
+      _ => 1.,
+    }
+  }
+}
+
+pub fn tick_enemy_traits(
+  mut enemies: Query<(&mut Enemy, &EnemyType, &mut EnemyTraits)>,
+  game_data: Res<GameData>,
+  enemy_stats: Res<Assets<EnemyTypeStats>>,
+  fixed_time: Res<FixedTime>,
+) {
+  let Some(enemy_stats) = enemy_stats.get(&game_data.enemy_type_stats)
+    else { return; };
+  let delta = fixed_time.period.as_secs_f32();
+
+  for (mut enemy, enemy_type, mut traits) in &mut enemies {
+    if traits.speed_burst.is_some() {
+      traits.elapsed += delta;
+    }
+
+    // Dead enemies don't come back
+    if traits.regeneration <= 0. || enemy.health <= 0 {
+      continue;
+    }
+    let max_health = enemy_stats.enemy[enemy_type].enemy.health;
+    if enemy.health >= max_health {
+      traits.regeneration_progress = 0.;
+      continue;
+    }
+
+    traits.regeneration_progress += traits.regeneration * delta;
+    let regenerated = traits.regeneration_progress.floor();
+    if regenerated >= 1. {
+      traits.regeneration_progress -= regenerated;
+      enemy.health = (enemy.health + regenerated as i32).min(max_health);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn shielded(shield: u32) -> EnemyTraits {
+    EnemyTraits {
+      shield,
+      ..default()
+    }
+  }
+
+  #[test]
+  fn shield_absorbs_damage_first() {
+    let mut traits = shielded(5);
+
+    // Fully absorbed
+    assert_eq!(traits.absorb(3), 0);
+    assert_eq!(traits.shield, 2);
+    // Partly absorbed, the rest goes to health
+    assert_eq!(traits.absorb(6), 4);
+    assert_eq!(traits.shield, 0);
+    // Depleted
+    assert_eq!(traits.absorb(4), 4);
+    assert_eq!(traits.shield, 0);
+  }
+
+  #[test]
+  fn no_shield_absorbs_nothing() {
+    let mut traits = shielded(0);
+    assert_eq!(traits.absorb(0), 0);
+    assert_eq!(traits.absorb(7), 7);
+
+    let mut traits = shielded(3);
+    assert_eq!(traits.absorb(0), 0);
+    assert_eq!(traits.shield, 3);
+  }
+
+  #[test]
+  fn speed_bursts_at_the_end_of_every_cycle() {
+    let mut traits = EnemyTraits {
+      speed_burst: Some(SpeedBurst {
+        every: 4.,
+        duration: 1.,
+        speed: 2.,
+      }),
+      ..default()
+    };
+
+    for (elapsed, speed) in [(0., 1.), (2.9, 1.), (3., 2.), (3.9, 2.), (4.1, 1.), (7.5, 2.)] {
+      traits.elapsed = elapsed;
+      assert_eq!(traits.speed_multiplier(), speed, "at {} seconds", elapsed);
+    }
+
+    // Without a burst, or with a zero cycle, speed never changes
+    assert_eq!(shielded(0).speed_multiplier(), 1.);
+    traits.speed_burst.as_mut().unwrap().every = 0.;
+    assert_eq!(traits.speed_multiplier(), 1.);
+  }
+}
@@ -7,6 +7,7 @@ use strum_macros::Display;
 use crate::assets::*;
 use crate::enemy::*;
 use crate::map::*;
+use crate::DamageType;
 
 #[derive(Component, Display, Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, Hash)]
 pub enum EnemyType {
@@ -18,17 +19,92 @@ pub enum EnemyType {
   Orange,
   Purple,
   Red,
+  // Bosses
+  GreenKing,
+  RedKing,
 }
 
 #[derive(Resource, Debug, Serialize, Deserialize, TypeUuid, Clone)]
 #[uuid = "7aad646e-4054-44d7-b138-1fb79f73f9c1"]
 pub struct EnemyTypeStats {
   pub enemy: HashMap<EnemyType, EnemyBundle>,
+  // Enemies released when an enemy of the type dies, with how many of each
+  #[serde(default)]
+  pub children: HashMap<EnemyType, Vec<(EnemyType, usize)>>,
+  // Damage beyond an enemy's health is dealt to its children
+  #[serde(default)]
+  pub overkill_carries_over: bool,
+  // Damage taken from each element, below 1 for resistances and above 1 for weaknesses
+  #[serde(default)]
+  pub damage_multipliers: HashMap<EnemyType, HashMap<DamageType, f32>>,
+  #[serde(default)]
+  pub bosses: HashMap<EnemyType, BossStats>,
+  // Money for killing an enemy of the type
+  #[serde(default)]
+  pub bounties: HashMap<EnemyType, usize>,
+  // Base health lost when an enemy of the type reaches the end
+  #[serde(default)]
+  pub leak_damage: HashMap<EnemyType, i32>,
+  // Leaking enemies also deal the leak damage of the children they would have released
+  #[serde(default)]
+  pub leak_damage_includes_children: bool,
+}
+
+// Bounty of enemy types that aren't listed
+const DEFAULT_BOUNTY: usize = 10;
+// Leak damage of enemy types that aren't listed
+const DEFAULT_LEAK_DAMAGE: i32 = 1;
+// Stops enemy types that are their own children from adding up forever
+const MAX_CHILD_DEPTH: usize = 16;
+
+impl EnemyTypeStats {
+  pub fn bounty(&self, enemy_type: EnemyType) -> usize {
+    self.bounties.get(&enemy_type).copied().unwrap_or(DEFAULT_BOUNTY)
+  }
+
+  pub fn leak_damage(&self, enemy_type: EnemyType) -> i32 {
+    self.leak_damage_at_depth(enemy_type, 0)
+  }
+
+  fn leak_damage_at_depth(&self, enemy_type: EnemyType, depth: usize) -> i32 {
+    let damage = self
+      .leak_damage
+      .get(&enemy_type)
+      .copied()
+      .unwrap_or(DEFAULT_LEAK_DAMAGE);
+    if !self.leak_damage_includes_children || depth >= MAX_CHILD_DEPTH {
+      return damage;
+    }
+
+    let children_damage = self
+      .children
+      .get(&enemy_type)
+      .into_iter()
+      .flatten()
+      .map(|(child_type, count)| self.leak_damage_at_depth(*child_type, depth + 1) * *count as i32)
+      .sum::<i32>();
+
+    damage + children_damage
+  }
+
+  pub fn damage_multiplier(&self, enemy_type: EnemyType, damage_type: DamageType) -> f32 {
+    self
+      .damage_multipliers
+      .get(&enemy_type)
+      .and_then(|multipliers| multipliers.get(&damage_type))
+      .copied()
+      .unwrap_or(1.)
+  }
 }
 
 impl EnemyType {
   pub fn get_enemy(&self, map_path: &Map, path: Path, enemy_stats: &EnemyTypeStats) -> EnemyBundle {
-    let direction = map_path.checkpoints[path.index + 1];
+    let direction = map_path
+      .paths
+      .get(path.route)
+      .and_then(|route| route.checkpoints.get(path.index + 1))
+      .copied()
+      .unwrap_or_default();
 
     let mut enemy_bundle = enemy_stats.enemy[self].clone();
 
@@ -48,6 +124,8 @@ impl EnemyType {
       EnemyType::Orange => TextureAtlasSprite::new(50),
       EnemyType::Purple => TextureAtlasSprite::new(60),
       EnemyType::Red => TextureAtlasSprite::new(70),
+      EnemyType::GreenKing => TextureAtlasSprite::new(0),
+      EnemyType::RedKing => TextureAtlasSprite::new(70),
     };
 
     SpriteSheetBundle {
@@ -58,3 +136,25 @@ impl EnemyType {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn total_damage(stats: &EnemyTypeStats, damage_type: DamageType, hits: usize) -> u32 {
+    let mut enemy = Enemy::new(100);
+    let multiplier = stats.damage_multiplier(EnemyType::Green, damage_type);
+
+    (0..hits).map(|_| enemy.scale_damage(1, multiplier)).sum()
+  }
+
+  #[test]
+  fn multipliers_apply_to_small_damage() {
+    let stats: EnemyTypeStats =
+      ron::from_str("(enemy: {}, damage_multipliers: {Green: {Fire: 1.5, Nature: 0.5}})").unwrap();
+
+    assert_eq!(total_damage(&stats, DamageType::Fire, 4), 6);
+    assert_eq!(total_damage(&stats, DamageType::Nature, 4), 2);
+    assert_eq!(total_damage(&stats, DamageType::Ice, 4), 4);
+  }
+}
@@ -0,0 +1,173 @@
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+
+use crate::enemy::*;
+use crate::{GameData, GameState, HealthBarSetting};
+
+const BAR_SIZE: Vec2 = Vec2::new(30., 4.);
+// Above the enemy sprite
+const BAR_OFFSET: Vec3 = Vec3::new(0., 20., 1.);
+
+pub struct HealthBarPlugin;
+
+impl Plugin for HealthBarPlugin {
+  fn build(&self, app: &mut App) {
+    app.add_systems(
+      (
+        spawn_health_bars,
+        update_health_bars.after(spawn_health_bars),
+        scale_health_bars.after(spawn_health_bars),
+      )
+        .in_set(OnUpdate(GameState::Gameplay)),
+    );
+  }
+}
+
+// Background of the bar, a child of the enemy
+#[derive(Component)]
+pub struct HealthBar {
+  pub max_health: i32,
+  pub fill: Entity,
+}
+
+#[derive(Component)]
+pub struct HealthBarFill;
+
+fn bar_visibility(setting: HealthBarSetting, health: i32, max_health: i32) -> Visibility {
+  let visible = match setting {
+    HealthBarSetting::Always => true,
+    HealthBarSetting::Damaged => health < max_health,
+    HealthBarSetting::Hidden => false,
+  };
+
+  if visible {
+    Visibility::Inherited
+  } else {
+    Visibility::Hidden
+  }
+}
+
+fn fill_scale(health: i32, max_health: i32) -> Vec3 {
+  Vec3::new((health as f32 / max_health.max(1) as f32).clamp(0., 1.), 1., 1.)
+}
+
+// Bosses are scaled up, keep the bar the same size and just above the enemy
+fn bar_transform(enemy_scale: Vec3) -> Transform {
+  let offset = BAR_OFFSET + Vec3::Y * ENEMY_SIZE.y / 2. * (enemy_scale.y - 1.);
+  Transform::from_translation(offset / enemy_scale).with_scale(Vec3::ONE / enemy_scale)
+}
+
+fn spawn_health_bars(
+  mut commands: Commands,
+  enemies: Query<(Entity, &Enemy, &EnemyType, &Transform), Added<Enemy>>,
+  game_data: Res<GameData>,
+  enemy_stats: Res<Assets<EnemyTypeStats>>,
+  setting: Res<HealthBarSetting>,
+) {
+  let Some(enemy_stats) = enemy_stats.get(&game_data.enemy_type_stats)
+    else { return; };
+
+  // Children and loaded enemies can start damaged
+  for (entity, enemy, enemy_type, transform) in &enemies {
+    let max_health = enemy_stats.enemy[enemy_type].enemy.health;
+
+    // Starts at the left edge, so scaling it down empties the bar to the left
+    let fill = commands
+      .spawn(SpriteBundle {
+        sprite: Sprite {
+          color: Color::rgb(0.2, 0.8, 0.2),
+          custom_size: Some(BAR_SIZE),
+          anchor: Anchor::CenterLeft,
+          ..default()
+        },
+        transform: Transform::from_xyz(-BAR_SIZE.x / 2., 0., 0.1)
+          .with_scale(fill_scale(enemy.health, max_health)),
+        ..default()
+      })
+      .insert(HealthBarFill)
+      .insert(Name::new("HealthBarFill"))
+      .id();
+
+    let bar = commands
+      .spawn(SpriteBundle {
+        sprite: Sprite {
+          color: Color::rgba(0., 0., 0., 0.7),
+          custom_size: Some(BAR_SIZE),
+          ..default()
+        },
+        transform: bar_transform(transform.scale),
+        visibility: bar_visibility(*setting, enemy.health, max_health),
+        ..default()
+      })
+      .insert(HealthBar { max_health, fill })
+      .insert(Name::new("HealthBar"))
+      .add_child(fill)
+      .id();
+
+    commands.entity(entity).add_child(bar);
+  }
+}
+
+// Only enemies whose health changed, or all of them when the setting changes
+fn update_health_bars(
+  enemies: Query<(Ref<Enemy>, &Children)>,
+  mut bars: Query<(&HealthBar, &mut Visibility)>,
+  mut fills: Query<&mut Transform, With<HealthBarFill>>,
+  setting: Res<HealthBarSetting>,
+) {
+  for (enemy, children) in &enemies {
+    if !enemy.is_changed() && !setting.is_changed() {
+      continue;
+    }
+
+    for &child in children.iter() {
+      let Ok((bar, mut visibility)) = bars.get_mut(child)
+        else { continue; };
+
+      *visibility = bar_visibility(*setting, enemy.health, bar.max_health);
+      if let Ok(mut transform) = fills.get_mut(bar.fill) {
+        transform.scale = fill_scale(enemy.health, bar.max_health);
+      }
+    }
+  }
+}
+
+// Bosses get their scale after spawning
+#[allow(clippy::type_complexity)]
+fn scale_health_bars(
+  enemies: Query<(&Transform, &Children), (With<Enemy>, Changed<Transform>)>,
+  mut bars: Query<&mut Transform, (With<HealthBar>, Without<Enemy>)>,
+) {
+  for (transform, children) in &enemies {
+    for &child in children.iter() {
+      let Ok(mut bar) = bars.get_mut(child)
+        else { continue; };
+
+      let expected = bar_transform(transform.scale);
+      if *bar != expected {
+        *bar = expected;
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn boss_bars_keep_their_size_and_gap() {
+    let unscaled = bar_transform(Vec3::ONE);
+    assert_eq!(unscaled.translation, BAR_OFFSET);
+
+    for scale in [2., 2.5] {
+      let enemy = Transform::from_scale(Vec3::splat(scale));
+      let bar = enemy.mul_transform(bar_transform(enemy.scale));
+      assert!((bar.scale - Vec3::ONE).length() < 1e-5);
+      // Same distance above the top of the scaled sprite
+      let gap = bar.translation.y - ENEMY_SIZE.y / 2. * scale;
+      assert!((gap - (BAR_OFFSET.y - ENEMY_SIZE.y / 2.)).abs() < 1e-4);
+      assert!((bar.translation.z - BAR_OFFSET.z).abs() < 1e-5);
+    }
+  }
+}
@@ -0,0 +1,324 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+use std::mem::discriminant;
+use std::time::Duration;
+
+use crate::enemy::*;
+use crate::{
+  bullet_enemy_collision, DamageType, GameData, GameState, SimulationSet, Tower, TowerId,
+};
+
+pub struct StatusEffectPlugin;
+
+impl Plugin for StatusEffectPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .add_system(
+        tick_status_effects
+          .after(bullet_enemy_collision)
+          .in_set(SimulationSet::Collision)
+          .in_schedule(CoreSchedule::FixedUpdate),
+      )
+      .add_system(tint_affected_enemies.in_set(OnUpdate(GameState::Gameplay)));
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum StatusEffectKind {
+  // Multiplies movement speed
+  Slow { speed: f32 },
+  // Damage every `interval` seconds
+  Burn { damage: u32, interval: f32 },
+  Poison { damage: u32, interval: f32 },
+  // Multiplies all damage taken
+  Curse { damage_taken: f32 },
+}
+
+// What happens when an effect is applied to an enemy that already has one of the same kind
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Stacking {
+  // One at a time, applying it again restarts it
+  #[default]
+  Refresh,
+  // Up to this many at once, applying another one when full restarts the oldest
+  Stack(usize),
+}
+
+// Effect a tower applies on hit - stats.tower_stats.ron
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "StatusEffectFile")]
+pub struct StatusEffect {
+  pub kind: StatusEffectKind,
+  // In seconds
+  pub duration: f32,
+  pub stacking: Stacking,
+}
+
+// Effect as written in the file, checked before the game uses it
+#[derive(Deserialize)]
+struct StatusEffectFile {
+  kind: StatusEffectKind,
+  duration: f32,
+  #[serde(default)]
+  stacking: Stacking,
+}
+
+impl TryFrom<StatusEffectFile> for StatusEffect {
+  type Error = String;
+
+  fn try_from(file: StatusEffectFile) -> Result<Self, Self::Error> {
+    // Timers panic on negative or NaN durations, and repeating ones on a zero interval
+    if Duration::try_from_secs_f32(file.duration).is_err() {
+      return Err(format!("duration must be 0 or more seconds, not {}", file.duration));
+    }
+    if let StatusEffectKind::Burn { interval, .. } | StatusEffectKind::Poison { interval, .. } =
+      file.kind
+    {
+      if Duration::try_from_secs_f32(interval).map_or(true, |interval| interval.is_zero()) {
+        return Err(format!("interval must be more than 0 seconds, not {}", interval));
+      }
+    }
+    if file.stacking == Stacking::Stack(0) {
+      return Err("Stack(0) can't hold any effects, use at least Stack(1)".to_string());
+    }
+
+    Ok(StatusEffect {
+      kind: file.kind,
+      duration: file.duration,
+      stacking: file.stacking,
+    })
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ActiveStatusEffect {
+  pub effect: StatusEffect,
+  // Tower credited with the damage over time
+  pub source: TowerId,
+  // Element of the tower, for the enemy's resistances
+  #[serde(default)]
+  pub damage_type: DamageType,
+  pub remaining: Timer,
+  // Next damage over time tick
+  pub tick: Timer,
+}
+
+#[derive(Component, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StatusEffects(pub Vec<ActiveStatusEffect>);
+
+impl StatusEffects {
+  pub fn apply(&mut self, effect: &StatusEffect, source: TowerId, damage_type: DamageType) {
+    let interval = match effect.kind {
+      StatusEffectKind::Burn { interval, .. } | StatusEffectKind::Poison { interval, .. } => interval,
+      _ => effect.duration,
+    };
+    let active = ActiveStatusEffect {
+      effect: effect.clone(),
+      source,
+      damage_type,
+      remaining: Timer::from_seconds(effect.duration, TimerMode::Once),
+      tick: Timer::from_seconds(interval, TimerMode::Repeating),
+    };
+
+    let same_kind = self
+      .0
+      .iter()
+      .enumerate()
+      .filter(|(_, other)| discriminant(&other.effect.kind) == discriminant(&effect.kind))
+      .map(|(index, _)| index)
+      .collect::<Vec<_>>();
+    let max = match effect.stacking {
+      Stacking::Refresh => 1,
+      Stacking::Stack(max) => max,
+    };
+
+    // Effects are kept in the order they were applied, so the oldest one is first
+    if !same_kind.is_empty() && same_kind.len() >= max {
+      self.0.remove(same_kind[0]);
+    }
+    self.0.push(active);
+  }
+
+  pub fn speed_multiplier(&self) -> f32 {
+    self
+      .0
+      .iter()
+      .map(|active| match active.effect.kind {
+        StatusEffectKind::Slow { speed } => speed,
+        _ => 1.,
+      })
+      .product()
+  }
+
+  pub fn damage_taken_multiplier(&self) -> f32 {
+    self
+      .0
+      .iter()
+      .map(|active| match active.effect.kind {
+        StatusEffectKind::Curse { damage_taken } => damage_taken,
+        _ => 1.,
+      })
+      .product()
+  }
+}
+
+// Deal damage over time, credited to the tower that applied it, and remove expired effects
+#[allow(clippy::type_complexity)]
+fn tick_status_effects(
+  mut enemies: Query<(
+    &mut Enemy,
+    &EnemyType,
+    &mut StatusEffects,
+    &mut EnemyTraits,
+    &mut LastHitBy,
+    Option<&Immune>,
+  )>,
+  mut towers: Query<(Entity, &TowerId, &mut Tower)>,
+  game_data: Res<GameData>,
+  enemy_stats: Res<Assets<EnemyTypeStats>>,
+  fixed_time: Res<FixedTime>,
+  // Reused every tick
+  mut tower_entities: Local<HashMap<TowerId, Entity>>,
+) {
+  let Some(enemy_stats) = enemy_stats.get(&game_data.enemy_type_stats)
+    else { return; };
+
+  tower_entities.clear();
+  tower_entities.extend(towers.iter().map(|(entity, tower_id, _)| (*tower_id, entity)));
+
+  for (mut enemy, enemy_type, mut status_effects, mut traits, mut last_hit_by, immune) in
+    &mut enemies
+  {
+    let damage_taken = status_effects.damage_taken_multiplier();
+
+    for active in status_effects.0.iter_mut() {
+      active.remaining.tick(fixed_time.period);
+
+      let (StatusEffectKind::Burn { damage, .. } | StatusEffectKind::Poison { damage, .. }) =
+        active.effect.kind
+        else { continue; };
+
+      active.tick.tick(fixed_time.period);
+      for _ in 0..active.tick.times_finished_this_tick() {
+        if enemy.health <= 0 || immune.is_some() {
+          break;
+        }
+
+        // Same resistances as the tower's bullets, then shields take the damage first
+        let multiplier = enemy_stats.damage_multiplier(*enemy_type, active.damage_type);
+        let damage = enemy.scale_damage(damage, multiplier * damage_taken);
+        let damage = traits.absorb(damage) as i32;
+        if let Some(&tower_entity) = tower_entities.get(&active.source) {
+          if let Ok((_, _, mut tower)) = towers.get_mut(tower_entity) {
+            tower.total_damage += damage.min(enemy.health) as u32;
+          }
+          last_hit_by.0 = Some(tower_entity);
+        }
+        enemy.health -= damage;
+      }
+    }
+
+    status_effects.0.retain(|active| !active.remaining.finished());
+  }
+}
+
+// Colour enemies by their latest effect
+fn tint_affected_enemies(
+  mut enemies: Query<(&StatusEffects, &EnemyTraits, &mut TextureAtlasSprite), Changed<StatusEffects>>,
+) {
+  for (status_effects, traits, mut sprite) in &mut enemies {
+    let color = match status_effects.0.last().map(|active| &active.effect.kind) {
+      Some(StatusEffectKind::Slow { .. }) => Color::rgb(0.6, 0.8, 1.),
+      Some(StatusEffectKind::Burn { .. }) => Color::rgb(1., 0.6, 0.4),
+      Some(StatusEffectKind::Poison { .. }) => Color::rgb(0.6, 1., 0.5),
+      Some(StatusEffectKind::Curse { .. }) => Color::rgb(0.8, 0.5, 1.),
+      None => Color::WHITE,
+    };
+    // Camo enemies are see-through
+    sprite.color = color.with_a(if traits.camo { 0.5 } else { 1. });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn effect(kind: StatusEffectKind, stacking: Stacking) -> StatusEffect {
+    StatusEffect {
+      kind,
+      duration: 2.,
+      stacking,
+    }
+  }
+
+  fn slow(speed: f32, stacking: Stacking) -> StatusEffect {
+    effect(StatusEffectKind::Slow { speed }, stacking)
+  }
+
+  fn sources(effects: &StatusEffects) -> Vec<u32> {
+    effects.0.iter().map(|active| active.source.0).collect()
+  }
+
+  #[test]
+  fn refresh_replaces_the_effect() {
+    let mut effects = StatusEffects::default();
+    effects.apply(&slow(0.5, Stacking::Refresh), TowerId(1), DamageType::Ice);
+    effects.0[0].remaining.tick(Duration::from_secs(1));
+    effects.apply(&slow(0.5, Stacking::Refresh), TowerId(2), DamageType::Ice);
+
+    assert_eq!(sources(&effects), vec![2]);
+    // Restarted
+    assert_eq!(effects.0[0].remaining.elapsed(), Duration::ZERO);
+    assert_eq!(effects.speed_multiplier(), 0.5);
+  }
+
+  #[test]
+  fn stacks_are_capped_and_drop_the_oldest() {
+    let mut effects = StatusEffects::default();
+    for source in 1..=5 {
+      effects.apply(&slow(0.5, Stacking::Stack(3)), TowerId(source), DamageType::Ice);
+    }
+    assert_eq!(sources(&effects), vec![3, 4, 5]);
+
+    // Other kinds don't count towards the cap
+    let curse = effect(StatusEffectKind::Curse { damage_taken: 1.5 }, Stacking::Refresh);
+    effects.apply(&curse, TowerId(6), DamageType::Dark);
+    effects.apply(&slow(0.5, Stacking::Stack(3)), TowerId(7), DamageType::Ice);
+    assert_eq!(sources(&effects), vec![4, 5, 6, 7]);
+  }
+
+  #[test]
+  fn multipliers_multiply_across_stacks() {
+    let mut effects = StatusEffects::default();
+    assert_eq!(effects.speed_multiplier(), 1.);
+    assert_eq!(effects.damage_taken_multiplier(), 1.);
+
+    let curse = effect(StatusEffectKind::Curse { damage_taken: 1.5 }, Stacking::Stack(2));
+    effects.apply(&slow(0.5, Stacking::Stack(3)), TowerId(1), DamageType::Ice);
+    effects.apply(&slow(0.8, Stacking::Stack(3)), TowerId(2), DamageType::Ice);
+    effects.apply(&curse, TowerId(3), DamageType::Dark);
+    effects.apply(&curse, TowerId(4), DamageType::Dark);
+
+    assert!((effects.speed_multiplier() - 0.4).abs() < 1e-6);
+    assert!((effects.damage_taken_multiplier() - 2.25).abs() < 1e-6);
+  }
+
+  #[test]
+  fn invalid_effects_are_rejected() {
+    let parse = |text: &str| ron::from_str::<StatusEffect>(text);
+
+    assert!(parse("(kind: Burn(damage: 1, interval: 0.5), duration: 3.0)").is_ok());
+    assert_eq!(
+      parse("(kind: Slow(speed: 0.5), duration: 3.0, stacking: Stack(2))")
+        .unwrap()
+        .stacking,
+      Stacking::Stack(2)
+    );
+
+    assert!(parse("(kind: Burn(damage: 1, interval: 0.0), duration: 3.0)").is_err());
+    assert!(parse("(kind: Poison(damage: 1, interval: -1.0), duration: 3.0)").is_err());
+    assert!(parse("(kind: Slow(speed: 0.5), duration: -1.0)").is_err());
+    assert!(parse("(kind: Slow(speed: 0.5), duration: 3.0, stacking: Stack(0))").is_err());
+  }
+}
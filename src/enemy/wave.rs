@@ -1,12 +1,14 @@
 use bevy::prelude::*;
 use bevy::reflect::TypeUuid;
-use serde::Deserialize;
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
 use std::time::Duration;
 
 use crate::assets::*;
 use crate::enemy::*;
 use crate::map::*;
-use crate::{GameData, GameState};
+use crate::{GameData, GameState, SimulationSet};
 
 pub struct WavePlugin;
 
@@ -14,65 +16,328 @@ impl Plugin for WavePlugin {
   fn build(&self, app: &mut App) {
     app
       .add_event::<WaveClearedEvent>()
-      .add_system(load_waves.in_schedule(OnExit(GameState::AssetLoading)))
-      .add_system(spawn_waves.in_set(OnUpdate(GameState::Gameplay)));
+      .add_event::<WaveCalledEarlyEvent>()
+      // Cleared by the tick, like the tower commands
+      .init_resource::<Events<NextWaveCommand>>()
+      .add_system(load_waves.in_schedule(OnExit(GameState::LevelLoading)))
+      .add_systems(
+        (call_next_wave, Events::<NextWaveCommand>::update_system)
+          .chain()
+          .in_set(SimulationSet::Commands)
+          .in_schedule(CoreSchedule::FixedUpdate),
+      )
+      .add_systems(
+        (clear_waves, spawn_waves)
+          .chain()
+          .in_set(SimulationSet::Spawn)
+          .in_schedule(CoreSchedule::FixedUpdate),
+      );
   }
 }
 
+// Every enemy of the wave has spawned and none are left
 pub struct WaveClearedEvent {
   pub index: usize,
 }
 
+// Wave an enemy belongs to, children and minions belong to the wave of their parent
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FromWave(pub usize);
+
+// Start the next wave now - Next Wave button or N
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NextWaveCommand;
+
+pub struct WaveCalledEarlyEvent {
+  pub index: usize,
+  // Money for the time skipped
+  pub bonus: usize,
+}
+
+// Seconds between waves when a wave doesn't set its `gap`
+const DEFAULT_WAVE_GAP: f32 = 10.;
+const DEFAULT_EARLY_CALL_BONUS: f32 = 2.;
+
 #[derive(Resource, Default, Deserialize, TypeUuid)]
 #[uuid = "2ee4097e-4768-40d6-962b-e7ad0b750219"]
 pub struct Waves {
   pub waves: Vec<Wave>,
+  // Newest wave that has started
   pub current: usize,
+  // Money per second skipped by calling the next wave early
+  #[serde(default = "default_early_call_bonus")]
+  pub early_call_bonus: f32,
+}
+
+fn default_early_call_bonus() -> f32 {
+  DEFAULT_EARLY_CALL_BONUS
 }
 
 impl Waves {
   pub fn current(&self) -> Option<&Wave> {
-    return self.waves.get(self.current);
+    self.waves.get(self.current)
   }
 
-  pub fn advance(
-    &mut self,
-    wave_cleared_writer: &mut EventWriter<WaveClearedEvent>,
-  ) -> Option<&Wave> {
-    wave_cleared_writer.send(WaveClearedEvent {
-      index: self.current,
-    });
+  pub fn advance(&mut self) -> Option<&Wave> {
     self.current += 1;
-    return self.current();
+    self.current()
+  }
+
+  // Money for calling the next wave now, none after the last wave
+  pub fn next_wave_bonus(&self, wave_state: &WaveState) -> Option<usize> {
+    if self.current + 1 >= self.waves.len() {
+      return None;
+    }
+    let skipped = wave_state.time_until_next_wave(self).as_secs_f32();
+
+    Some((skipped * self.early_call_bonus).round() as usize)
   }
 }
 
-#[derive(Component, Deserialize)]
-#[derive(Default)]
+// A wave is written as a list of enemies, `(enemies: [(Green, (secs: 1, nanos: 0)), ...])`,
+// as groups, `(groups: [(enemy: Green, count: 35, interval: 1.0)])`, or both.
+// Either way it is expanded into a spawn schedule when loaded
+#[derive(Component, Deserialize, Default)]
+#[serde(try_from = "WaveFile")]
 pub struct Wave {
-  pub enemies: Vec<(EnemyType, Duration)>,
-  pub current: usize, // Current enemy
+  // Ordered by spawn time
+  pub spawns: Vec<WaveSpawn>,
+  // Wait after the last spawn before the next wave
+  pub gap: Duration,
+}
+
+#[derive(Deserialize)]
+struct WaveFile {
+  #[serde(default)]
+  enemies: Vec<WaveEnemy>,
+  #[serde(default)]
+  groups: Vec<WaveGroup>,
+  // Seconds
+  #[serde(default = "default_wave_gap")]
+  gap: f32,
+}
+
+fn default_wave_gap() -> f32 {
+  DEFAULT_WAVE_GAP
+}
+
+// Seconds from a waves file, negative or not a number fails to load instead of panicking
+fn seconds(value: f32, name: &str) -> Result<Duration, String> {
+  Duration::try_from_secs_f32(value)
+    .map_err(|_| format!("{} must be 0 or more seconds, not {}", name, value))
+}
+
+impl TryFrom<WaveFile> for Wave {
+  type Error = String;
+
+  fn try_from(file: WaveFile) -> Result<Self, Self::Error> {
+    let mut spawns = vec![];
+
+    // The list runs one enemy after the other from the start of the wave
+    let mut time = Duration::ZERO;
+    for enemy in file.enemies {
+      spawns.push(WaveSpawn {
+        time,
+        enemy_type: enemy.enemy_type,
+        path: enemy.path,
+      });
+      time += enemy.delay;
+    }
+
+    // Groups run at the same time as the list and each other
+    for group in file.groups {
+      seconds(group.interval, "interval")?;
+      seconds(group.start_delay, "start_delay")?;
+      for index in 0..group.count {
+        spawns.push(WaveSpawn {
+          time: seconds(group.start_delay + group.interval * index as f32, "group spawn time")?,
+          enemy_type: group.enemy,
+          path: group.path.clone(),
+        });
+      }
+    }
+
+    // Stable, so enemies spawning together keep the order they were written in
+    spawns.sort_by_key(|spawn| spawn.time);
+
+    Ok(Self {
+      spawns,
+      gap: seconds(file.gap, "gap")?,
+    })
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WaveSpawn {
+  // Since the start of the wave
+  pub time: Duration,
+  pub enemy_type: EnemyType,
+  pub path: Option<String>,
+}
+
+// `count` enemies, `interval` seconds apart, starting `start_delay` seconds into the wave
+#[derive(Clone, Debug, Deserialize)]
+pub struct WaveGroup {
+  pub enemy: EnemyType,
+  pub count: usize,
+  pub interval: f32,
+  #[serde(default)]
+  pub start_delay: f32,
+  #[serde(default)]
+  pub path: Option<String>,
+}
+
+// `(Green, (secs: 1, nanos: 0))` or, to pick the map path, `(Green, (secs: 1, nanos: 0), "North")`.
+// Enemies without a path take turns on the map's paths
+#[derive(Clone, Debug)]
+pub struct WaveEnemy {
+  pub enemy_type: EnemyType,
+  // Time until the next enemy spawns
+  pub delay: Duration,
+  pub path: Option<String>,
 }
 
+impl<'de> Deserialize<'de> for WaveEnemy {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    struct WaveEnemyVisitor;
+
+    impl<'de> Visitor<'de> for WaveEnemyVisitor {
+      type Value = WaveEnemy;
 
+      fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a tuple of enemy type, delay and an optional path name")
+      }
 
-#[derive(Resource)]
+      fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<WaveEnemy, A::Error> {
+        let enemy_type = seq
+          .next_element()?
+          .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let delay = seq
+          .next_element()?
+          .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let path = seq.next_element()?;
+
+        Ok(WaveEnemy {
+          enemy_type,
+          delay,
+          path,
+        })
+      }
+    }
+
+    deserializer.deserialize_tuple(3, WaveEnemyVisitor)
+  }
+}
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
 pub struct WaveState {
+  // Gap after the last spawn of the newest wave, then the next wave starts
   pub wave_spawn_timer: Timer,
-  pub enemy_spawn_timer: Timer,
-  pub remaining: usize,
+  // Waves with enemies left to spawn. A wave called early
+  // spawns alongside the ones before it
+  #[serde(default)]
+  pub spawning: Vec<SpawningWave>,
+  // Started waves that haven't been cleared yet
+  #[serde(default)]
+  pub uncleared: Vec<usize>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpawningWave {
+  pub index: usize,
+  // Since the wave started
+  pub elapsed: Duration,
+  // Index of the next spawn in the wave's schedule
+  pub next_spawn: usize,
 }
 
-impl From<(&Wave, usize)> for WaveState {
-  fn from(wave: (&Wave, usize)) -> Self {
+impl Default for WaveState {
+  fn default() -> Self {
     Self {
-      wave_spawn_timer: Timer::new(Duration::from_secs(10), TimerMode::Once),
-      enemy_spawn_timer: Timer::new(wave.0.enemies[wave.0.current].1, TimerMode::Repeating),
-      remaining: wave.1,
+      wave_spawn_timer: Timer::from_seconds(DEFAULT_WAVE_GAP, TimerMode::Once),
+      spawning: vec![],
+      uncleared: vec![],
     }
   }
 }
 
+impl WaveState {
+  fn start(&mut self, index: usize, wave: &Wave) {
+    self.spawning.push(SpawningWave {
+      index,
+      elapsed: Duration::ZERO,
+      next_spawn: 0,
+    });
+    self.uncleared.push(index);
+    self.wave_spawn_timer = Timer::new(wave.gap, TimerMode::Once);
+  }
+
+  // Spawn time left in the newest wave plus its gap
+  pub fn time_until_next_wave(&self, waves: &Waves) -> Duration {
+    let spawn_time_left = self
+      .spawning
+      .iter()
+      .find(|spawning| spawning.index == waves.current)
+      .and_then(|spawning| {
+        let last_spawn = waves.waves.get(spawning.index)?.spawns.last()?;
+        Some(last_spawn.time.saturating_sub(spawning.elapsed))
+      })
+      .unwrap_or_default();
+
+    spawn_time_left + self.wave_spawn_timer.remaining()
+  }
+}
+
+// Start the next wave straight away and pay for the time skipped
+pub fn call_next_wave(
+  mut next_wave_commands: EventReader<NextWaveCommand>,
+  game_data: Res<GameData>,
+  mut waves: ResMut<Assets<Waves>>,
+  mut wave_state: ResMut<WaveState>,
+  mut called_early_writer: EventWriter<WaveCalledEarlyEvent>,
+) {
+  let Some(waves) = waves.get_mut(&game_data.enemy_waves)
+    else { return; };
+
+  for _ in next_wave_commands.iter() {
+    let Some(bonus) = waves.next_wave_bonus(&wave_state)
+      else { continue; };
+
+    let index = waves.current + 1;
+    let Some(wave) = waves.advance()
+      else { continue; };
+    wave_state.start(index, wave);
+
+    info!("WAVE {} CALLED EARLY: {} BONUS", index + 1, bonus);
+    called_early_writer.send(WaveCalledEarlyEvent { index, bonus });
+  }
+}
+
+// Runs before spawn_waves, so enemies spawned last tick are already in the world
+fn clear_waves(
+  mut wave_state: ResMut<WaveState>,
+  enemies: Query<&FromWave>,
+  mut wave_cleared_writer: EventWriter<WaveClearedEvent>,
+) {
+  let WaveState {
+    spawning,
+    uncleared,
+    ..
+  } = &mut *wave_state;
+
+  uncleared.retain(|&index| {
+    let cleared = !spawning.iter().any(|spawning| spawning.index == index)
+      && !enemies.iter().any(|from_wave| from_wave.0 == index);
+    if cleared {
+      info!("WAVE {} CLEARED", index + 1);
+      wave_cleared_writer.send(WaveClearedEvent { index });
+    }
+
+    !cleared
+  });
+}
+
+#[allow(clippy::too_many_arguments)]
 fn spawn_waves(
   mut commands: Commands,
   assets: Res<GameAssets>, // Tower and enemy assets
@@ -81,67 +346,236 @@ fn spawn_waves(
   mut waves: ResMut<Assets<Waves>>,
   mut wave_state: ResMut<WaveState>,
   enemy_type_assets: Res<Assets<EnemyTypeStats>>,
-  time: Res<Time>,
-  mut wave_cleared_writer: EventWriter<WaveClearedEvent>,
+  fixed_time: Res<FixedTime>,
 ) {
   let Some(map_path) = map.get(&game_data.map)
     else { return; };
   let Some(waves) = waves.get_mut(&game_data.enemy_waves)
     else { return; };
+  let Some(enemy_stats) = enemy_type_assets.get(&game_data.enemy_type_stats)
+    else { return; };
 
-  // If all enemies in wave have finished, if button has been pressed
-  // or if in-between waves timer has finished !!!
-  if wave_state.remaining == 0 {
-    wave_state.wave_spawn_timer.tick(time.delta());
-    if !wave_state.wave_spawn_timer.just_finished() {
-      return;
-    }
-    if let Some(next_wave) = waves.advance(&mut wave_cleared_writer) {
-      wave_state.remaining = next_wave.enemies.len(); // !!!
-      commands.insert_resource(WaveState::from((next_wave, next_wave.enemies.len())));
+  // Once every enemy of the newest wave has spawned, wait for the next wave
+  let current = waves.current;
+  if !wave_state.spawning.iter().any(|spawning| spawning.index == current) {
+    wave_state.wave_spawn_timer.tick(fixed_time.period);
+    if wave_state.wave_spawn_timer.just_finished() {
+      if let Some(wave) = waves.advance() {
+        wave_state.start(current + 1, wave);
+      }
     }
   }
 
-  let Some(current_wave) = waves.current() else {
-    return;
-  };
+  for spawning in wave_state.spawning.iter_mut() {
+    let Some(wave) = waves.waves.get(spawning.index)
+      else { continue; };
 
-  wave_state.enemy_spawn_timer.tick(time.delta());
-  if !wave_state.enemy_spawn_timer.just_finished() {
-    return;
-  }
-  //if wave_state.remaining > 0 { // !!!
-  let index = current_wave.enemies.len() - wave_state.remaining;
-  //println!("Enemy #{}", (current_wave.enemies.len() - wave_state.remaining + 1));
+    spawning.elapsed += fixed_time.period;
 
-  let Some(enemy_stats) = enemy_type_assets.get(&game_data.enemy_type_stats)
-    else { return; };
+    // Several enemies can spawn on the same tick when groups overlap
+    while let Some(spawn) = wave.spawns.get(spawning.next_spawn) {
+      if spawn.time > spawning.elapsed {
+        break;
+      }
+
+      let spawn_index = spawning.next_spawn;
+      spawning.next_spawn += 1;
 
-  spawn_enemy(
-    &mut commands,
-    map_path,
-    current_wave.enemies[index].0,
-    &assets,
-    map_path.checkpoints[0],
-    Path { index: 0 },
-    enemy_stats,
-  );
+      let named_route = spawn.path.as_ref().and_then(|name| {
+        let route = map_path.path_index(name);
+        if route.is_none() {
+          warn!("Unknown path {}, spawning on the next path", name);
+        }
+        route
+      });
+      // Every path has checkpoints, a map without paths can't spawn anything
+      let Some((route, start)) = named_route
+        .or_else(|| map_path.route_for(spawn_index))
+        .and_then(|route| Some((route, *map_path.paths[route].checkpoints.first()?)))
+        else {
+          warn!("No path to spawn {:?} on", spawn.enemy_type);
+          continue;
+        };
 
-  wave_state.enemy_spawn_timer = Timer::new(current_wave.enemies[index].1, TimerMode::Repeating);
+      let enemy = spawn_enemy(
+        &mut commands,
+        map_path,
+        spawn.enemy_type,
+        &assets,
+        start,
+        Path { index: 0, route },
+        enemy_stats,
+      );
+      commands.entity(enemy).insert(FromWave(spawning.index));
+    }
+  }
 
-  wave_state.remaining -= 1;
-  //}
+  wave_state.spawning.retain(|spawning| {
+    waves
+      .waves
+      .get(spawning.index)
+      .is_some_and(|wave| spawning.next_spawn < wave.spawns.len())
+  });
 }
 
 fn load_waves(mut commands: Commands, game_data: Res<GameData>, waves: Res<Assets<Waves>>) {
-  let Some(waves) = waves.get(&game_data.enemy_waves)
-    else { return; };
+  let mut wave_state = WaveState::default();
+  // The first wave starts straight away
+  if let Some(first_wave) = waves
+    .get(&game_data.enemy_waves)
+    .and_then(|waves| waves.waves.first())
+  {
+    wave_state.start(0, first_wave);
+  }
+  commands.insert_resource(wave_state);
+}
 
-  let num_enemies = waves.waves[0].enemies.len();
+#[cfg(test)]
+mod tests {
+  use super::*;
 
-  commands.insert_resource(WaveState {
-    wave_spawn_timer: Timer::new(Duration::from_secs(10), TimerMode::Once),
-    enemy_spawn_timer: Timer::new(Duration::from_millis(1), TimerMode::Repeating),
-    remaining: num_enemies,
-  });
+  fn wave(ron: &str) -> Wave {
+    ron::from_str(ron).unwrap()
+  }
+
+  #[test]
+  fn list_and_groups_give_the_same_schedule() {
+    // Wave [2] of enemy.waves.ron, before it was written as groups
+    let list = wave(
+      "(enemies: [
+        (Green, (secs: 1, nanos: 0)), (Green, (secs: 1, nanos: 0)), (Green, (secs: 1, nanos: 0)),
+        (Green, (secs: 1, nanos: 0)), (Green, (secs: 1, nanos: 0)), (Green, (secs: 1, nanos: 0)),
+        (Green, (secs: 1, nanos: 0)), (Green, (secs: 1, nanos: 0)), (Green, (secs: 1, nanos: 0)),
+        (Green, (secs: 2, nanos: 0)),
+        (Yellow, (secs: 2, nanos: 0)), (Yellow, (secs: 2, nanos: 0)), (Yellow, (secs: 2, nanos: 0)),
+        (Yellow, (secs: 2, nanos: 0)), (Yellow, (secs: 2, nanos: 0)),
+        (Green, (secs: 1, nanos: 0)), (Green, (secs: 1, nanos: 0)), (Green, (secs: 1, nanos: 0)),
+        (Green, (secs: 1, nanos: 0)), (Green, (secs: 1, nanos: 0)), (Green, (secs: 1, nanos: 0)),
+        (Green, (secs: 1, nanos: 0)), (Green, (secs: 1, nanos: 0)), (Green, (secs: 1, nanos: 0)),
+        (Green, (secs: 1, nanos: 0)), (Green, (secs: 1, nanos: 0)), (Green, (secs: 1, nanos: 0)),
+        (Green, (secs: 1, nanos: 0)), (Green, (secs: 1, nanos: 0)), (Green, (secs: 1, nanos: 0)),
+        (GreenKing, (secs: 3, nanos: 0)),
+      ])",
+    );
+    let groups = wave(
+      "(groups: [
+        (enemy: Green, count: 10, interval: 1.0),
+        (enemy: Yellow, count: 5, interval: 2.0, start_delay: 11.0),
+        (enemy: Green, count: 15, interval: 1.0, start_delay: 21.0),
+        (enemy: GreenKing, count: 1, interval: 0.0, start_delay: 36.0),
+      ])",
+    );
+
+    assert_eq!(list.spawns.len(), 31);
+    assert_eq!(list.spawns, groups.spawns);
+  }
+
+  #[test]
+  fn list_and_groups_run_at_the_same_time() {
+    let wave = wave(
+      "(enemies: [(Green, (secs: 2, nanos: 0)), (Green, (secs: 2, nanos: 0))],
+        groups: [(enemy: Red, count: 2, interval: 1.0, start_delay: 0.5, path: Some(\"East\"))])",
+    );
+
+    let schedule = wave
+      .spawns
+      .iter()
+      .map(|spawn| (spawn.time.as_secs_f32(), spawn.enemy_type, spawn.path.as_deref()))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      schedule,
+      vec![
+        (0., EnemyType::Green, None),
+        (0.5, EnemyType::Red, Some("East")),
+        (1.5, EnemyType::Red, Some("East")),
+        (2., EnemyType::Green, None),
+      ]
+    );
+  }
+
+  #[test]
+  fn invalid_times_are_rejected() {
+    for ron in [
+      "(groups: [(enemy: Green, count: 2, interval: -1.0)])",
+      "(groups: [(enemy: Green, count: 2, interval: 1.0, start_delay: -5.0)])",
+      "(groups: [(enemy: Green, count: 2, interval: NaN)])",
+      "(groups: [], gap: -1.0)",
+    ] {
+      assert!(ron::from_str::<Wave>(ron).is_err(), "{}", ron);
+    }
+  }
+
+  #[test]
+  fn gap_defaults_to_ten_seconds() {
+    assert_eq!(wave("(groups: [])").gap, Duration::from_secs(10));
+  }
+
+  // Three waves of 5 enemies 2 seconds apart, the last one spawns at 8 seconds
+  fn waves() -> Waves {
+    Waves {
+      waves: (0..3)
+        .map(|_| wave("(groups: [(enemy: Green, count: 5, interval: 2.0)], gap: 10.0)"))
+        .collect(),
+      current: 0,
+      early_call_bonus: 2.,
+    }
+  }
+
+  fn started(waves: &Waves) -> WaveState {
+    let mut wave_state = WaveState::default();
+    wave_state.start(waves.current, &waves.waves[waves.current]);
+    wave_state
+  }
+
+  #[test]
+  fn calling_mid_spawn_pays_for_the_spawns_and_gap_left() {
+    let waves = waves();
+    let mut wave_state = started(&waves);
+    wave_state.spawning[0].elapsed = Duration::from_secs(3);
+
+    // 5 seconds of spawns and the whole 10 second gap
+    assert_eq!(wave_state.time_until_next_wave(&waves), Duration::from_secs(15));
+    assert_eq!(waves.next_wave_bonus(&wave_state), Some(30));
+  }
+
+  #[test]
+  fn calling_during_the_gap_pays_for_the_gap_left() {
+    let waves = waves();
+    let mut wave_state = started(&waves);
+    wave_state.spawning.clear();
+    wave_state.wave_spawn_timer.tick(Duration::from_secs(4));
+
+    assert_eq!(wave_state.time_until_next_wave(&waves), Duration::from_secs(6));
+    assert_eq!(waves.next_wave_bonus(&wave_state), Some(12));
+
+    // Nothing left to skip
+    wave_state.wave_spawn_timer.tick(Duration::from_secs(6));
+    assert_eq!(waves.next_wave_bonus(&wave_state), Some(0));
+  }
+
+  #[test]
+  fn only_the_newest_wave_counts() {
+    let mut waves = waves();
+    let mut wave_state = started(&waves);
+    wave_state.spawning[0].elapsed = Duration::from_secs(1);
+
+    // Wave 2 called early, wave 1 is still spawning alongside it
+    waves.advance();
+    wave_state.start(1, &waves.waves[1]);
+    wave_state.spawning[1].elapsed = Duration::from_secs(6);
+
+    assert_eq!(wave_state.time_until_next_wave(&waves), Duration::from_secs(12));
+    assert_eq!(waves.next_wave_bonus(&wave_state), Some(24));
+  }
+
+  #[test]
+  fn no_bonus_after_the_last_wave() {
+    let mut waves = waves();
+    waves.advance();
+    waves.advance();
+    let wave_state = started(&waves);
+
+    assert_eq!(waves.current, 2);
+    assert_eq!(waves.next_wave_bonus(&wave_state), None);
+  }
 }
@@ -1,21 +1,37 @@
 use bevy::prelude::*;
 use bevy_asset_loader::prelude::*;
+use bevy_common_assets::ron::RonAssetPlugin;
 
-use crate::{EnemyTypeStats, Map, TowerTypeStats, Upgrades, Waves};
+use crate::{EnemyTypeStats, GameState, Levels, Map, TiledMapLoader, TowerTypeStats, Upgrades, Waves};
 
-#[derive(Resource)]
-struct EnemyTypeStatsHandle(Handle<EnemyTypeStats>);
+pub struct GameDataPlugin;
+
+impl Plugin for GameDataPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .add_plugin(RonAssetPlugin::<EnemyTypeStats>::new(&["enemy_types.ron"]))
+      .add_plugin(RonAssetPlugin::<Levels>::new(&["levels.ron"]))
+      .add_plugin(RonAssetPlugin::<Map>::new(&["map.ron"]))
+      // Maps can also be made in Tiled, .tmj or .tmx
+      .add_asset_loader(TiledMapLoader)
+      .add_plugin(RonAssetPlugin::<TowerTypeStats>::new(&["tower_stats.ron"]))
+      .add_plugin(RonAssetPlugin::<Upgrades>::new(&["upgrades.ron"]))
+      .add_plugin(RonAssetPlugin::<Waves>::new(&["waves.ron"]))
+      .add_collection_to_loading_state::<_, GameData>(GameState::AssetLoading);
+  }
+}
 
 #[derive(AssetCollection, Resource)]
 pub struct GameData {
   #[asset(path = "data/stats.enemy_types.ron")]
   pub enemy_type_stats: Handle<EnemyTypeStats>,
-  #[asset(path = "data/level1.map.ron")]
+  #[asset(path = "data/game.levels.ron")]
+  pub levels: Handle<Levels>,
+  // Map and waves of the selected level, loaded when the level is chosen
   pub map: Handle<Map>,
   #[asset(path = "data/stats.tower_stats.ron")]
   pub tower_type_stats: Handle<TowerTypeStats>,
   #[asset(path = "data/tower.upgrades.ron")]
   pub tower_upgrades: Handle<Upgrades>,
-  #[asset(path = "data/enemy.waves.ron")]
   pub enemy_waves: Handle<Waves>,
 }
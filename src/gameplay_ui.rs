@@ -1,6 +1,7 @@
 mod base;
 pub use base::*;
 
+#[allow(clippy::module_inception)]
 mod gameplay_ui;
 pub use gameplay_ui::*;
 
@@ -1,4 +1,4 @@
-use crate::GameState;
+use crate::{CurrentLevel, GameState};
 use bevy::prelude::*;
 
 pub struct BasePlugin;
@@ -17,15 +17,20 @@ pub struct Base {
   pub health: i32,
 }
 
-fn spawn_base(mut commands: Commands) {
-  commands.spawn((Base { health: 100 }, Name::new("Base")));
+fn spawn_base(mut commands: Commands, level: Res<CurrentLevel>) {
+  commands.spawn((
+    Base {
+      health: level.0.base_health,
+    },
+    Name::new("Base"),
+  ));
 }
 
-pub fn damage_base(commands: &mut Commands, entity: &Entity, enemy_health: i32, base: &mut Base) {
+pub fn damage_base(commands: &mut Commands, entity: &Entity, damage: i32, base: &mut Base) {
   commands.entity(*entity).despawn_recursive();
 
-  if base.health > enemy_health {
-    base.health -= enemy_health;
+  if base.health > damage {
+    base.health -= damage;
   } else {
     base.health = 0;
     info!("GAME OVER");
@@ -2,7 +2,10 @@ use bevy::prelude::*;
 
 use crate::assets::*;
 use crate::gameplay_ui::*;
-use crate::{GameData, GameState, Waves};
+use crate::{
+  Boss, Enemy, EnemyLeakedEvent, GameData, GameState, Immune, NextWaveCommand, ReplayPlayback,
+  WaveState, Waves,
+};
 
 #[derive(Component)]
 pub struct GameplayUIRoot;
@@ -16,16 +19,46 @@ pub struct MoneyUI;
 #[derive(Component)]
 pub struct RoundUI;
 
+// Seconds the health counter stays red after an enemy leaks
+const LEAK_FLASH_SECONDS: f32 = 0.5;
+
+// Shown while a boss is alive
+#[derive(Component)]
+pub struct BossHealthBar;
+
+#[derive(Component)]
+pub struct BossHealthFill;
+
+#[derive(Component)]
+pub struct BossNameUI;
+
+// Starts the next wave early, hidden after the last wave
+#[derive(Component)]
+pub struct NextWaveButton;
+
+#[derive(Component)]
+pub struct NextWaveText;
+
 pub struct GameplayUIPlugin;
 
 impl Plugin for GameplayUIPlugin {
   fn build(&self, app: &mut App) {
     app
       .add_system(spawn_gameplay_ui.in_schedule(OnEnter(GameState::Gameplay)))
-      .add_system(update_gameplay_ui.in_set(OnUpdate(GameState::Gameplay)));
+      .add_systems(
+        (
+          update_gameplay_ui,
+          update_boss_health_bar,
+          flash_health_on_leak,
+          update_next_wave_button,
+          next_wave_input.run_if(not(resource_exists::<ReplayPlayback>())),
+        )
+          .in_set(OnUpdate(GameState::Gameplay)),
+      );
   }
 }
 
+#[allow(clippy::type_complexity)]
 fn update_gameplay_ui(
   player: Query<&Player>,
   base: Query<&Base>,
@@ -54,6 +87,107 @@ fn update_gameplay_ui(
   }
 }
 
+// Turn the health counter red and fade it back to white
+fn flash_health_on_leak(
+  mut leak_events: EventReader<EnemyLeakedEvent>,
+  mut health_ui: Query<&mut Text, With<HealthUI>>,
+  time: Res<Time>,
+  mut flash_remaining: Local<f32>,
+) {
+  if leak_events.iter().count() > 0 {
+    *flash_remaining = LEAK_FLASH_SECONDS;
+  }
+  if *flash_remaining <= 0. {
+    return;
+  }
+  // Real time, so the flash doesn't speed up with the replay speed
+  *flash_remaining = (*flash_remaining - time.raw_delta_seconds()).max(0.);
+
+  let Ok(mut health) = health_ui.get_single_mut()
+    else { return; };
+  let red = *flash_remaining / LEAK_FLASH_SECONDS;
+  health.sections[0].style.color = Color::rgb(1., 1. - red, 1. - red);
+}
+
+fn update_boss_health_bar(
+  bosses: Query<(&Boss, &Enemy, Option<&Immune>)>,
+  mut health_bar: Query<&mut Visibility, With<BossHealthBar>>,
+  mut health_fill: Query<(&mut Style, &mut BackgroundColor), With<BossHealthFill>>,
+  mut boss_name: Query<&mut Text, With<BossNameUI>>,
+) {
+  let Ok(mut visibility) = health_bar.get_single_mut()
+    else { return; };
+
+  // The first boss still alive, when there are several
+  let Some((boss, enemy, immune)) = bosses.iter().find(|(_, enemy, _)| enemy.health > 0)
+    else {
+      *visibility = Visibility::Hidden;
+      return;
+    };
+  *visibility = Visibility::Visible;
+
+  let (mut style, mut color) = health_fill.single_mut();
+  let health = enemy.health as f32 / boss.max_health.max(1) as f32;
+  style.size.width = Val::Percent(health.clamp(0., 1.) * 100.);
+  // Grey while the boss can't be damaged
+  *color = if immune.is_some() {
+    Color::GRAY.into()
+  } else {
+    Color::rgb(0.8, 0.1, 0.1).into()
+  };
+
+  let mut name = boss_name.single_mut();
+  if name.sections[0].value != boss.name {
+    name.sections[0].value = boss.name.clone();
+  }
+}
+
+// Next Wave button or N
+fn next_wave_input(
+  keys: Res<Input<KeyCode>>,
+  interactions: Query<&Interaction, (With<NextWaveButton>, Changed<Interaction>)>,
+  mut next_wave_commands: EventWriter<NextWaveCommand>,
+) {
+  let clicked = interactions
+    .iter()
+    .any(|interaction| matches!(interaction, Interaction::Clicked));
+
+  if clicked || keys.just_pressed(KeyCode::N) {
+    next_wave_commands.send(NextWaveCommand);
+  }
+}
+
+// Shows the bonus for calling the next wave now
+fn update_next_wave_button(
+  game_data: Res<GameData>,
+  waves: Res<Assets<Waves>>,
+  wave_state: Option<Res<WaveState>>,
+  replay: Option<Res<ReplayPlayback>>,
+  mut button: Query<&mut Visibility, With<NextWaveButton>>,
+  mut text: Query<&mut Text, With<NextWaveText>>,
+) {
+  let Ok(mut visibility) = button.get_single_mut()
+    else { return; };
+  let bonus = waves
+    .get(&game_data.enemy_waves)
+    .zip(wave_state)
+    .and_then(|(waves, wave_state)| waves.next_wave_bonus(&wave_state));
+
+  // Replays call their waves themselves
+  let Some(bonus) = bonus.filter(|_| replay.is_none())
+    else {
+      *visibility = Visibility::Hidden;
+      return;
+    };
+  *visibility = Visibility::Inherited;
+
+  let mut text = text.single_mut();
+  let value = format!("Next Wave +${}", bonus);
+  if text.sections[0].value != value {
+    text.sections[0].value = value;
+  }
+}
+
 fn spawn_gameplay_ui(mut commands: Commands, assets: Res<GameAssets>) {
   commands
     .spawn(NodeBundle {
@@ -190,4 +324,91 @@ fn spawn_gameplay_ui(mut commands: Commands, assets: Res<GameAssets>) {
         });
     })
     .insert(Name::new("GameplayUI"));
+
+  commands
+    .spawn(NodeBundle {
+      style: Style {
+        size: Size::new(Val::Percent(50.0), Val::Px(28.0)),
+        position_type: PositionType::Absolute,
+        position: UiRect {
+          left: Val::Percent(25.0),
+          top: Val::Percent(12.0),
+          ..default()
+        },
+        ..default()
+      },
+      background_color: Color::rgba(0., 0., 0., 0.6).into(),
+      visibility: Visibility::Hidden,
+      ..default()
+    })
+    .insert(BossHealthBar)
+    .with_children(|commands| {
+      commands
+        .spawn(NodeBundle {
+          style: Style {
+            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            ..default()
+          },
+          background_color: Color::rgb(0.8, 0.1, 0.1).into(),
+          ..default()
+        })
+        .insert(BossHealthFill)
+        .insert(Name::new("BossHealthFill"));
+
+      commands
+        .spawn(TextBundle {
+          style: Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+              left: Val::Px(8.0),
+              ..default()
+            },
+            ..default()
+          },
+          text: Text::from_section(
+            "",
+            TextStyle {
+              font: assets.font.clone(),
+              font_size: 24.,
+              color: Color::WHITE,
+            },
+          ),
+          ..default()
+        })
+        .insert(BossNameUI)
+        .insert(Name::new("BossName"));
+    })
+    .insert(Name::new("BossHealthBar"));
+  commands
+    .spawn(ButtonBundle {
+      style: Style {
+        size: Size::new(Val::Px(220.), Val::Px(50.)),
+        position_type: PositionType::Absolute,
+        position: UiRect {
+          right: Val::Percent(2.),
+          top: Val::Percent(12.),
+          ..default()
+        },
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+      },
+      background_color: BackgroundColor(Color::DARK_GREEN),
+      visibility: Visibility::Hidden,
+      ..default()
+    })
+    .insert(NextWaveButton)
+    .with_children(|commands| {
+      commands
+        .spawn(TextBundle::from_section(
+          "",
+          TextStyle {
+            font: assets.font.clone(),
+            font_size: 30.,
+            color: Color::WHITE,
+          },
+        ))
+        .insert(NextWaveText);
+    })
+    .insert(Name::new("NextWaveButton"));
 }
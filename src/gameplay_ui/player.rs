@@ -1,6 +1,8 @@
 use bevy::prelude::*;
 
-use crate::{EnemyDeathEvent, GameState, WaveClearedEvent};
+use crate::{
+  CurrentLevel, EnemyDeathEvent, GameState, SimulationSet, WaveCalledEarlyEvent, WaveClearedEvent,
+};
 
 pub struct PlayerPlugin;
 
@@ -10,8 +12,14 @@ impl Plugin for PlayerPlugin {
       .register_type::<Player>()
       .add_system(spawn_player.in_schedule(OnEnter(GameState::Gameplay)))
       .add_systems(
-        (give_money_on_enemy_death, give_money_on_wave_cleared)
-          .in_set(OnUpdate(GameState::Gameplay)),
+        (
+          give_money_on_enemy_death,
+          give_money_on_wave_cleared,
+          give_money_on_early_call,
+        )
+          .chain()
+          .in_set(SimulationSet::Rewards)
+          .in_schedule(CoreSchedule::FixedUpdate),
       );
   }
 }
@@ -22,8 +30,13 @@ pub struct Player {
   pub money: usize,
 }
 
-fn spawn_player(mut commands: Commands) {
-  commands.spawn((Player { money: 100 }, Name::new("Player")));
+fn spawn_player(mut commands: Commands, level: Res<CurrentLevel>) {
+  commands.spawn((
+    Player {
+      money: level.0.starting_money,
+    },
+    Name::new("Player"),
+  ));
 }
 
 fn give_money_on_enemy_death(
@@ -31,8 +44,8 @@ fn give_money_on_enemy_death(
   mut death_events: EventReader<EnemyDeathEvent>,
 ) {
   let mut player = player.single_mut();
-  for _ in death_events.iter() {
-    player.money += 10;
+  for death in death_events.iter() {
+    player.money += death.bounty;
   }
 }
 
@@ -45,3 +58,13 @@ fn give_money_on_wave_cleared(
     player.money += wave.index + 101;
   }
 }
+
+fn give_money_on_early_call(
+  mut player: Query<&mut Player>,
+  mut called_early_events: EventReader<WaveCalledEarlyEvent>,
+) {
+  let mut player = player.single_mut();
+  for called_early in called_early_events.iter() {
+    player.money += called_early.bonus;
+  }
+}
@@ -0,0 +1,3 @@
+#[allow(clippy::module_inception)]
+mod headless;
+pub use headless::*;
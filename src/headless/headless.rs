@@ -0,0 +1,124 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::time::{TimeSystem, TimeUpdateStrategy};
+use bevy::utils::Instant;
+use bevy_asset_loader::prelude::*;
+use std::fs;
+
+use crate::{
+  Base, BasePlugin, BossPlugin, BulletPlugin, Enemy, EnemyGridPlugin, EnemyPlugin, EnemyTraitsPlugin, GameAssets, GameData, GameDataPlugin,
+  GameState, LevelPlugin, MapPlugin, MovementPlugin, Player, PlayerPlugin, ReplayPlugin, SimulationPlugin,
+  StatusEffectPlugin, TowerCommandPlugin, TowerPlugin, WavePlugin, WaveState, Waves, SIMULATION_TIMESTEP,
+};
+
+// Written when the simulation ends, the console isn't visible in Windows builds
+pub const HEADLESS_RESULT_PATH: &str = "headless_result.txt";
+
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .insert_resource(TimeUpdateStrategy::ManualInstant(Instant::now()))
+      .add_system(
+        advance_simulated_time
+          .in_base_set(CoreSet::First)
+          .before(TimeSystem),
+      )
+      // No textures are loaded, so every sprite bundle gets an empty handle
+      .init_resource::<GameAssets>()
+      .add_system(finish_simulation.in_set(OnUpdate(GameState::Gameplay)))
+      // Only reached when the level couldn't be loaded
+      .add_system(exit_on_main_menu.in_schedule(OnEnter(GameState::MainMenu)));
+  }
+}
+
+// Step time by exactly one gameplay tick per update instead of waiting for the wall clock,
+// so a whole game runs as fast as possible
+fn advance_simulated_time(mut time_update_strategy: ResMut<TimeUpdateStrategy>) {
+  if let TimeUpdateStrategy::ManualInstant(instant) = time_update_strategy.as_mut() {
+    *instant += SIMULATION_TIMESTEP;
+  }
+}
+
+// Run all waves without a window and write the result
+pub fn run_headless() {
+  App::new()
+    .add_plugins(MinimalPlugins)
+    .add_plugin(bevy::log::LogPlugin::default())
+    .add_plugin(TransformPlugin)
+    .add_plugin(HierarchyPlugin)
+    .add_plugin(bevy::asset::AssetPlugin::default())
+    // Game State
+    .add_state::<GameState>()
+    // Asset loading - skip the menus and go straight into the level given with `--level`
+    .add_loading_state(
+      LoadingState::new(GameState::AssetLoading).continue_to_state(GameState::LevelLoading),
+    )
+    .add_plugin(GameDataPlugin)
+    // Plugins
+    .add_plugin(HeadlessPlugin)
+    .add_plugin(LevelPlugin)
+    .add_plugin(SimulationPlugin)
+    .add_plugin(ReplayPlugin)
+    .add_plugin(MapPlugin)
+    .add_plugin(PlayerPlugin)
+    .add_plugin(BasePlugin)
+    .add_plugin(TowerPlugin)
+    .add_plugin(TowerCommandPlugin)
+    .add_plugin(EnemyPlugin)
+    .add_plugin(StatusEffectPlugin)
+    .add_plugin(BossPlugin)
+    .add_plugin(EnemyTraitsPlugin)
+    .add_plugin(EnemyGridPlugin)
+    .add_plugin(WavePlugin)
+    .add_plugin(BulletPlugin)
+    .add_plugin(MovementPlugin)
+    .run();
+}
+
+fn exit_on_main_menu(mut exit: EventWriter<AppExit>) {
+  error!("Headless simulation stopped, the level couldn't be loaded");
+  exit.send(AppExit);
+}
+
+// Stop once the base is destroyed or every wave has been spawned, defeated and paid out
+fn finish_simulation(
+  game_data: Res<GameData>,
+  waves: Res<Assets<Waves>>,
+  wave_state: Res<WaveState>,
+  enemies: Query<(), With<Enemy>>,
+  player: Query<&Player>,
+  base: Query<&Base>,
+  mut exit: EventWriter<AppExit>,
+) {
+  let Some(waves) = waves.get(&game_data.enemy_waves)
+    else { return; };
+
+  let player = player.single();
+  let base = base.single();
+
+  let waves_finished =
+    waves.current >= waves.waves.len() && enemies.is_empty() && wave_state.uncleared.is_empty();
+  let waves_cleared = (0..waves.current.min(waves.waves.len()))
+    .filter(|index| !wave_state.uncleared.contains(index))
+    .count();
+
+  if base.health <= 0 || waves_finished {
+    let result = format!(
+      "Base health: {}\nMoney: {}\nWaves cleared: {}/{}\n",
+      base.health,
+      player.money,
+      waves_cleared,
+      waves.waves.len()
+    );
+    for line in result.lines() {
+      info!("{}", line);
+    }
+    if let Err(err) = fs::write(HEADLESS_RESULT_PATH, result) {
+      error!("Could not write {}: {}", HEADLESS_RESULT_PATH, err);
+    }
+
+    exit.send(AppExit);
+  }
+}
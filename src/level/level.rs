@@ -0,0 +1,103 @@
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::{Deserialize, Serialize};
+
+use crate::{report_map_errors, GameData, GameState, Map};
+
+pub struct LevelPlugin;
+
+impl Plugin for LevelPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .insert_resource(SelectedLevel(level_from_args().unwrap_or(0)))
+      .add_system(start_level_loading.in_schedule(OnEnter(GameState::LevelLoading)))
+      .add_system(finish_level_loading.in_set(OnUpdate(GameState::LevelLoading)));
+  }
+}
+
+// Level registry - data/game.levels.ron
+#[derive(Resource, Serialize, Deserialize, TypeUuid)]
+#[uuid = "0d8c2a5e-5b7e-4f5d-9a4c-3c1f0e6b7a21"]
+pub struct Levels {
+  pub levels: Vec<Level>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Level {
+  pub name: String,
+  // Asset paths, relative to the assets folder
+  pub map: String,
+  pub waves: String,
+  pub starting_money: usize,
+  pub base_health: i32,
+}
+
+// Index of the level to play in the level registry
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SelectedLevel(pub usize);
+
+// Level being played
+#[derive(Resource, Clone, Debug)]
+pub struct CurrentLevel(pub Level);
+
+// Read the level index from `--level <number>`
+fn level_from_args() -> Option<usize> {
+  let args: Vec<String> = std::env::args().collect();
+  let index = args.iter().position(|arg| arg == "--level")?;
+
+  args.get(index + 1)?.parse().ok()
+}
+
+// Load the map and waves of the selected level
+fn start_level_loading(
+  mut commands: Commands,
+  asset_server: Res<AssetServer>,
+  mut game_data: ResMut<GameData>,
+  levels: Res<Assets<Levels>>,
+  selected_level: Res<SelectedLevel>,
+  mut game_state: ResMut<NextState<GameState>>,
+) {
+  let Some(level) = levels
+    .get(&game_data.levels)
+    .and_then(|levels| levels.levels.get(selected_level.0))
+    else {
+      error!("Level {} is not in the level registry", selected_level.0);
+      game_state.set(GameState::MainMenu);
+      return;
+    };
+
+  info!("LEVEL: {}", level.name);
+
+  game_data.map = asset_server.load(level.map.as_str());
+  game_data.enemy_waves = asset_server.load(level.waves.as_str());
+  commands.insert_resource(CurrentLevel(level.clone()));
+}
+
+// Start the level once its files are loaded and the map has no errors
+fn finish_level_loading(
+  asset_server: Res<AssetServer>,
+  game_data: Res<GameData>,
+  maps: Res<Assets<Map>>,
+  current_level: Res<CurrentLevel>,
+  mut game_state: ResMut<NextState<GameState>>,
+) {
+  match asset_server.get_group_load_state([game_data.map.id(), game_data.enemy_waves.id()]) {
+    LoadState::Loaded => {
+      let Some(map) = maps.get(&game_data.map)
+        else { return; };
+
+      // Maps are checked once, before load_map flips the tiles
+      if !map.paths.is_empty() || report_map_errors(&current_level.0.map, map) {
+        game_state.set(GameState::Gameplay);
+      } else {
+        game_state.set(GameState::MainMenu);
+      }
+    }
+    LoadState::Failed => {
+      error!("Could not load the level's map or waves");
+      game_state.set(GameState::MainMenu);
+    }
+    _ => {}
+  }
+}
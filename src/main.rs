@@ -1,7 +1,7 @@
 #![windows_subsystem = "windows"] // Disable console
 use bevy::prelude::*;
 use bevy_asset_loader::prelude::*;
-use bevy_common_assets::ron::RonAssetPlugin;
+use bevy_ecs_tilemap::TilemapPlugin;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 
 mod map;
@@ -20,8 +20,30 @@ mod movement;
 pub use movement::*;
 mod game_data;
 pub use game_data::*;
+mod headless;
+pub use headless::*;
+mod simulation;
+pub use simulation::*;
+mod save_game;
+pub use save_game::*;
+mod replay;
+pub use replay::*;
+mod level;
+pub use level::*;
+mod map_editor;
+pub use map_editor::*;
 
 fn main() {
+  // Run the simulation without a window, e.g. on build servers
+  if std::env::args().any(|arg| arg == "--headless") {
+    run_headless();
+    return;
+  }
+  // Check a map file for errors and exit
+  if let Some(path) = check_map_path_from_args() {
+    std::process::exit(check_map_file(&path));
+  }
+
   App::new()
     // Background of window. Set colour of screen on each refresh
     .insert_resource(ClearColor(Color::rgb(0.1, 0.1, 0.1)))
@@ -44,36 +66,46 @@ fn main() {
     // Game State
     .add_state::<GameState>()
     // Asset loading
-    .add_plugin(RonAssetPlugin::<EnemyTypeStats>::new(&["enemy_types.ron"]))
-    .add_plugin(RonAssetPlugin::<Map>::new(&["map.ron"]))
-    .add_plugin(RonAssetPlugin::<TowerTypeStats>::new(&["tower_stats.ron"]))
-    .add_plugin(RonAssetPlugin::<Upgrades>::new(&["upgrades.ron"]))
-    .add_plugin(RonAssetPlugin::<Waves>::new(&["waves.ron"]))
     .add_loading_state(
       LoadingState::new(GameState::AssetLoading).continue_to_state(GameState::MainMenu),
     )
-    .add_collection_to_loading_state::<_, GameData>(GameState::AssetLoading)
+    .add_plugin(GameDataPlugin)
     // Plugins
+    .add_plugin(LevelPlugin)
+    .add_plugin(SimulationPlugin)
+    .add_plugin(ReplayPlugin)
     .add_plugin(MainMenuPlugin)
+    .add_plugin(LevelSelectPlugin)
     .add_plugin(GameplayUIPlugin)
+    .add_plugin(TilemapPlugin)
     .add_plugin(MapPlugin)
+    .add_plugin(CameraControlPlugin)
     //.add_plugin(SpawnScenePlugin)
     .add_plugin(SettingsPlugin)
     .add_plugin(AssetPlugin)
     .add_plugin(PlayerPlugin)
     .add_plugin(BasePlugin)
     .add_plugin(TowerPlugin)
+    .add_plugin(TowerCommandPlugin)
     .add_plugin(TowerButtonPlugin)
     .add_plugin(TowerSelectionPlugin)
     .add_plugin(TowerUIPlugin)
     .add_plugin(EnemyPlugin)
+    .add_plugin(StatusEffectPlugin)
+    .add_plugin(BossPlugin)
+    .add_plugin(EnemyTraitsPlugin)
+    .add_plugin(EnemyGridPlugin)
+    .add_plugin(HealthBarPlugin)
     .add_plugin(WavePlugin)
     .add_plugin(BulletPlugin)
     .add_plugin(MovementPlugin)
+    .add_plugin(SaveGamePlugin)
+    .add_plugin(ReplayUIPlugin)
+    .add_plugin(MapEditorPlugin)
     // !!!Debugging
     .add_plugin(WorldInspectorPlugin::new())
     .add_plugin(bevy::diagnostic::LogDiagnosticsPlugin::default())
-    .add_plugin(bevy::diagnostic::FrameTimeDiagnosticsPlugin::default())
+    .add_plugin(bevy::diagnostic::FrameTimeDiagnosticsPlugin)
     .add_plugin(bevy::diagnostic::EntityCountDiagnosticsPlugin)
     .run();
 }
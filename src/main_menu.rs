@@ -1,8 +1,12 @@
 mod game_state;
 pub use game_state::*;
 
+#[allow(clippy::module_inception)]
 mod main_menu;
 pub use main_menu::*;
 
 mod settings;
 pub use settings::*;
+
+mod level_select;
+pub use level_select::*;
@@ -5,5 +5,8 @@ pub enum GameState {
   #[default]
   AssetLoading,
   MainMenu,
+  LevelSelect,
+  LevelLoading,
   Gameplay,
+  MapEditor,
 }
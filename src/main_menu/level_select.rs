@@ -0,0 +1,129 @@
+use bevy::prelude::*;
+
+use crate::assets::*;
+use crate::main_menu::*;
+use crate::{GameData, Levels, SelectedLevel};
+
+pub struct LevelSelectPlugin;
+
+impl Plugin for LevelSelectPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .add_system(spawn_level_select.in_schedule(OnEnter(GameState::LevelSelect)))
+      .add_system(despawn_level_select.in_schedule(OnExit(GameState::LevelSelect)))
+      .add_systems(
+        (level_button_clicked, back_button_clicked).in_set(OnUpdate(GameState::LevelSelect)),
+      );
+  }
+}
+
+#[derive(Component)]
+pub struct LevelSelectUIRoot;
+
+#[derive(Component)]
+pub struct LevelButton {
+  pub index: usize,
+}
+
+#[derive(Component)]
+pub struct BackButton;
+
+fn level_button_clicked(
+  mut commands: Commands,
+  interactions: Query<(&Interaction, &LevelButton), Changed<Interaction>>,
+  mut game_state: ResMut<NextState<GameState>>,
+) {
+  for (interaction, level_button) in &interactions {
+    if matches!(interaction, Interaction::Clicked) {
+      commands.insert_resource(SelectedLevel(level_button.index));
+      game_state.set(GameState::LevelLoading);
+    }
+  }
+}
+
+fn back_button_clicked(
+  interactions: Query<&Interaction, (With<BackButton>, Changed<Interaction>)>,
+  mut game_state: ResMut<NextState<GameState>>,
+) {
+  for interaction in &interactions {
+    if matches!(interaction, Interaction::Clicked) {
+      game_state.set(GameState::MainMenu);
+    }
+  }
+}
+
+fn despawn_level_select(mut commands: Commands, root: Query<Entity, With<LevelSelectUIRoot>>) {
+  for entity in &root {
+    commands.entity(entity).despawn_recursive();
+  }
+}
+
+fn spawn_level_select(
+  mut commands: Commands,
+  assets: Res<GameAssets>,
+  game_data: Res<GameData>,
+  levels: Res<Assets<Levels>>,
+) {
+  let Some(levels) = levels.get(&game_data.levels)
+    else { return; };
+
+  let level_buttons = levels
+    .levels
+    .iter()
+    .enumerate()
+    .map(|(index, level)| {
+      let button = spawn_text_button(
+        &mut commands,
+        &assets,
+        &level.name,
+        Color::DARK_GREEN,
+        TEXT_BUTTON_HEIGHT,
+      );
+      commands
+        .entity(button)
+        .insert(LevelButton { index })
+        .insert(Name::new(format!("Level {}", index)));
+      button
+    })
+    .collect::<Vec<_>>();
+
+  let back_button =
+    spawn_text_button(&mut commands, &assets, "Back", Color::MAROON, TEXT_BUTTON_HEIGHT);
+  commands
+    .entity(back_button)
+    .insert(BackButton)
+    .insert(Name::new("BackButton"));
+
+  commands
+    .spawn(NodeBundle {
+      style: Style {
+        size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+        justify_content: JustifyContent::Center,
+        flex_direction: FlexDirection::Column,
+        ..default()
+      },
+      ..default()
+    })
+    .insert(LevelSelectUIRoot)
+    .insert(Name::new("LevelSelect"))
+    .with_children(|commands| {
+      commands.spawn(TextBundle {
+        style: Style {
+          align_self: AlignSelf::Center,
+          margin: UiRect::all(Val::Percent(3.)),
+          ..default()
+        },
+        text: Text::from_section(
+          "Select Level",
+          TextStyle {
+            font: assets.font.clone(),
+            font_size: 90.,
+            color: Color::CYAN,
+          },
+        ),
+        ..default()
+      });
+    })
+    .push_children(&level_buttons)
+    .add_child(back_button);
+}
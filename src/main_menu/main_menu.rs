@@ -2,6 +2,7 @@ use bevy::{app::AppExit, prelude::*};
 
 use crate::assets::*;
 use crate::main_menu::*;
+use crate::{read_snapshot, PendingSnapshot, SelectedLevel, SAVE_PATH};
 
 pub struct MainMenuPlugin;
 
@@ -10,7 +11,13 @@ impl Plugin for MainMenuPlugin {
     app
       .add_system(spawn_main_menu.in_schedule(OnEnter(GameState::MainMenu)))
       .add_systems(
-        (start_button_clicked, exit_button_clicked).in_set(OnUpdate(GameState::MainMenu)),
+        (
+          start_button_clicked,
+          continue_button_clicked,
+          map_editor_button_clicked,
+          exit_button_clicked,
+        )
+          .in_set(OnUpdate(GameState::MainMenu)),
       );
   }
 }
@@ -21,6 +28,12 @@ pub struct MenuUIRoot;
 #[derive(Component)]
 pub struct StartButton;
 
+#[derive(Component)]
+pub struct ContinueButton;
+
+#[derive(Component)]
+pub struct MapEditorButton;
+
 #[derive(Component)]
 pub struct ExitButton;
 
@@ -35,7 +48,45 @@ fn start_button_clicked(
       let root_entity = menu_root.single();
       commands.entity(root_entity).despawn_recursive();
 
-      game_state.set(GameState::Gameplay);
+      game_state.set(GameState::LevelSelect);
+    }
+  }
+}
+
+// Resume the saved game
+fn continue_button_clicked(
+  mut commands: Commands,
+  interactions: Query<&Interaction, (With<ContinueButton>, Changed<Interaction>)>,
+  menu_root: Query<Entity, With<MenuUIRoot>>,
+  mut game_state: ResMut<NextState<GameState>>,
+) {
+  for interaction in &interactions {
+    if matches!(interaction, Interaction::Clicked) {
+      let Some(snapshot) = read_snapshot(SAVE_PATH)
+        else { return; };
+      commands.insert_resource(SelectedLevel(snapshot.level));
+      commands.insert_resource(PendingSnapshot(snapshot));
+
+      let root_entity = menu_root.single();
+      commands.entity(root_entity).despawn_recursive();
+
+      game_state.set(GameState::LevelLoading);
+    }
+  }
+}
+
+fn map_editor_button_clicked(
+  mut commands: Commands,
+  interactions: Query<&Interaction, (With<MapEditorButton>, Changed<Interaction>)>,
+  menu_root: Query<Entity, With<MenuUIRoot>>,
+  mut game_state: ResMut<NextState<GameState>>,
+) {
+  for interaction in &interactions {
+    if matches!(interaction, Interaction::Clicked) {
+      let root_entity = menu_root.single();
+      commands.entity(root_entity).despawn_recursive();
+
+      game_state.set(GameState::MapEditor);
     }
   }
 }
@@ -61,6 +112,23 @@ fn spawn_main_menu(mut commands: Commands, assets: Res<GameAssets>) {
     .id();
   commands.entity(start_button).insert(StartButton);
 
+  // Only offer to continue if there is a saved game
+  let continue_button = std::path::Path::new(SAVE_PATH).exists().then(|| {
+    let continue_button =
+      spawn_text_button(&mut commands, &assets, "Continue", Color::DARK_GREEN, TEXT_BUTTON_HEIGHT);
+    commands.entity(continue_button).insert(ContinueButton);
+    continue_button
+  });
+
+  let map_editor_button = spawn_text_button(
+    &mut commands,
+    &assets,
+    "Map Editor",
+    Color::DARK_GREEN,
+    TEXT_BUTTON_HEIGHT,
+  );
+  commands.entity(map_editor_button).insert(MapEditorButton);
+
   let exit_button = commands
     .spawn(ButtonBundle {
       style: spawn_button_style(),
@@ -70,7 +138,7 @@ fn spawn_main_menu(mut commands: Commands, assets: Res<GameAssets>) {
     .id();
   commands.entity(exit_button).insert(ExitButton);
 
-  commands
+  let menu_root = commands
     .spawn(NodeBundle {
       style: Style {
         size: Size::new(Val::Percent(100.), Val::Percent(100.)),
@@ -100,9 +168,53 @@ fn spawn_main_menu(mut commands: Commands, assets: Res<GameAssets>) {
       });
     })
     .add_child(start_button)
+    .id();
+
+  if let Some(continue_button) = continue_button {
+    commands.entity(menu_root).add_child(continue_button);
+  }
+
+  commands
+    .entity(menu_root)
+    .add_child(map_editor_button)
     .add_child(exit_button);
 }
 
+// Text buttons are shorter than the textured ones
+pub(crate) const TEXT_BUTTON_HEIGHT: f32 = 80.;
+
+// Button without a texture, for menu entries that don't have one
+pub(crate) fn spawn_text_button(
+  commands: &mut Commands,
+  assets: &GameAssets,
+  text: &str,
+  color: Color,
+  height: f32,
+) -> Entity {
+  let style = spawn_button_style();
+  commands
+    .spawn(ButtonBundle {
+      style: Style {
+        size: Size::new(style.size.width, Val::Px(height)),
+        align_items: AlignItems::Center,
+        ..style
+      },
+      background_color: BackgroundColor(color),
+      ..default()
+    })
+    .with_children(|commands| {
+      commands.spawn(TextBundle::from_section(
+        text,
+        TextStyle {
+          font: assets.font.clone(),
+          font_size: 50.,
+          color: Color::WHITE,
+        },
+      ));
+    })
+    .id()
+}
+
 fn spawn_button_style() -> Style {
   Style {
     size: Size::new(Val::Px(570.), Val::Px(147.)),
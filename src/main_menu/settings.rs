@@ -4,10 +4,23 @@ pub struct SettingsPlugin;
 
 impl Plugin for SettingsPlugin {
   fn build(&self, app: &mut App) {
-    app.add_system(toggle_vsync).add_system(toggle_fullscreen);
+    app
+      .init_resource::<HealthBarSetting>()
+      .add_system(toggle_vsync)
+      .add_system(toggle_fullscreen)
+      .add_system(cycle_health_bars);
   }
 }
 
+// Health bars above enemies
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HealthBarSetting {
+  Always,
+  #[default]
+  Damaged,
+  Hidden,
+}
+
 fn toggle_vsync(input: Res<Input<KeyCode>>, mut windows: Query<&mut Window>) {
   if input.just_pressed(KeyCode::V) {
     let mut window = windows.single_mut();
@@ -36,3 +49,14 @@ fn toggle_fullscreen(input: Res<Input<KeyCode>>, mut windows: Query<&mut Window>
     info!("WINDOW_MODE: {:?}", window.mode);
   }
 }
+
+fn cycle_health_bars(input: Res<Input<KeyCode>>, mut setting: ResMut<HealthBarSetting>) {
+  if input.just_pressed(KeyCode::H) {
+    *setting = match *setting {
+      HealthBarSetting::Always => HealthBarSetting::Damaged,
+      HealthBarSetting::Damaged => HealthBarSetting::Hidden,
+      HealthBarSetting::Hidden => HealthBarSetting::Always,
+    };
+    info!("HEALTH_BARS: {:?}", *setting);
+  }
+}
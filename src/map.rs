@@ -1,2 +1,11 @@
+#[allow(clippy::module_inception)]
 mod map;
 pub use map::*;
+mod map_validation;
+pub use map_validation::*;
+mod camera;
+pub use camera::*;
+mod tiled;
+pub use tiled::*;
+mod path_curve;
+pub use path_curve::*;
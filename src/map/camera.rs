@@ -0,0 +1,170 @@
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+use bevy::prelude::*;
+use bevy::render::camera::CameraProjection;
+
+use crate::map::*;
+use crate::{GameData, GameState};
+
+// Camera speed in screen pixels per second, so it feels the same at every zoom level
+const PAN_SPEED: f32 = 800.;
+// Distance from the window edge that scrolls the camera
+const EDGE_SCROLL_MARGIN: f32 = 10.;
+const ZOOM_STEP: f32 = 1.1;
+const MIN_ZOOM: f32 = 0.5;
+
+// Pan - WASD/Arrows, window edges, middle mouse drag. Zoom - mouse wheel
+pub struct CameraControlPlugin;
+
+impl Plugin for CameraControlPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .add_systems(
+        (pan_camera, zoom_camera, clamp_camera.after(pan_camera).after(zoom_camera))
+          .in_set(OnUpdate(GameState::Gameplay)),
+      )
+      .add_system(reset_camera_zoom.in_schedule(OnExit(GameState::Gameplay)));
+  }
+}
+
+// World units per screen pixel
+fn world_per_pixel(window: &Window, projection: &OrthographicProjection) -> f32 {
+  projection.area.width() / window.width()
+}
+
+fn pan_camera(
+  keys: Res<Input<KeyCode>>,
+  mouse: Res<Input<MouseButton>>,
+  time: Res<Time>,
+  windows: Query<&Window>,
+  mut camera: Query<(&mut Transform, &OrthographicProjection), With<MainCamera>>,
+  mut last_drag_position: Local<Option<Vec2>>,
+) {
+  let window = windows.get_single().unwrap();
+  let Ok((mut transform, projection)) = camera.get_single_mut()
+    else { return; };
+
+  let mut direction = Vec2::ZERO;
+  if keys.any_pressed([KeyCode::W, KeyCode::Up]) {
+    direction.y += 1.;
+  }
+  if keys.any_pressed([KeyCode::S, KeyCode::Down]) {
+    direction.y -= 1.;
+  }
+  if keys.any_pressed([KeyCode::A, KeyCode::Left]) {
+    direction.x -= 1.;
+  }
+  if keys.any_pressed([KeyCode::D, KeyCode::Right]) {
+    direction.x += 1.;
+  }
+
+  // Window position starts at the bottom left corner
+  if let Some(cursor) = window.cursor_position() {
+    if cursor.x <= EDGE_SCROLL_MARGIN {
+      direction.x -= 1.;
+    } else if cursor.x >= window.width() - EDGE_SCROLL_MARGIN {
+      direction.x += 1.;
+    }
+    if cursor.y <= EDGE_SCROLL_MARGIN {
+      direction.y -= 1.;
+    } else if cursor.y >= window.height() - EDGE_SCROLL_MARGIN {
+      direction.y += 1.;
+    }
+  }
+
+  let world_per_pixel = world_per_pixel(window, projection);
+  // Real time, so replay speed doesn't change the camera speed
+  let movement = direction.clamp_length_max(1.) * PAN_SPEED * time.raw_delta_seconds();
+  transform.translation += (movement * world_per_pixel).extend(0.);
+
+  // Drag the map along with the cursor
+  if mouse.pressed(MouseButton::Middle) {
+    if let Some(cursor) = window.cursor_position() {
+      if let Some(last_position) = *last_drag_position {
+        transform.translation -= ((cursor - last_position) * world_per_pixel).extend(0.);
+      }
+      *last_drag_position = Some(cursor);
+    }
+  } else {
+    *last_drag_position = None;
+  }
+}
+
+// Zoom towards the cursor, until the whole map fits on the screen
+fn zoom_camera(
+  mut scroll: EventReader<MouseWheel>,
+  windows: Query<&Window>,
+  game_data: Res<GameData>,
+  maps: Res<Assets<Map>>,
+  mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+) {
+  let window = windows.get_single().unwrap();
+  let Some(map) = maps.get(&game_data.map)
+    else { return; };
+  let Ok((mut transform, mut projection)) = camera.get_single_mut()
+    else { return; };
+
+  let lines = scroll
+    .iter()
+    .map(|event| match event.unit {
+      MouseScrollUnit::Line => event.y,
+      MouseScrollUnit::Pixel => event.y / 100.,
+    })
+    .sum::<f32>();
+  if lines == 0. {
+    return;
+  }
+
+  // Size of the view without zoom
+  let view_size = projection.area.size() / projection.scale;
+  let map_size = Vec2::new(map.width as f32, map.height as f32) * map.tile_size as f32;
+  let max_zoom = (map_size / view_size).max_element().max(1.);
+
+  let old_world_per_pixel = world_per_pixel(window, projection.as_ref());
+  let scale = (projection.scale * ZOOM_STEP.powf(-lines)).clamp(MIN_ZOOM, max_zoom);
+  let new_world_per_pixel = old_world_per_pixel * scale / projection.scale;
+  projection.scale = scale;
+  // The visible area is otherwise only updated after this frame
+  projection.update(window.width(), window.height());
+
+  // Keep the point under the cursor in place
+  if let Some(cursor) = window.cursor_position() {
+    let from_center = cursor - Vec2::new(window.width(), window.height()) / 2.;
+    transform.translation += (from_center * (old_world_per_pixel - new_world_per_pixel)).extend(0.);
+  }
+}
+
+// Keep the view inside the map, or centered on it when the map is smaller than the view
+fn clamp_camera(
+  windows: Query<&Window>,
+  game_data: Res<GameData>,
+  maps: Res<Assets<Map>>,
+  mut camera: Query<(&mut Transform, &OrthographicProjection), With<MainCamera>>,
+) {
+  let window = windows.get_single().unwrap();
+  let Some(map) = maps.get(&game_data.map)
+    else { return; };
+  let Ok((mut transform, projection)) = camera.get_single_mut()
+    else { return; };
+
+  // Tiles are centered on their position
+  let tile_size = map.tile_size as f32;
+  let map_min = Vec2::splat(-tile_size / 2.);
+  let map_max = Vec2::new(map.width as f32, map.height as f32) * tile_size + map_min;
+  let half_view = Vec2::new(window.width(), window.height()) * world_per_pixel(window, projection) / 2.;
+
+  let mut position = transform.translation.truncate();
+  for axis in 0..2 {
+    position[axis] = if map_max[axis] - map_min[axis] <= half_view[axis] * 2. {
+      (map_min[axis] + map_max[axis]) / 2.
+    } else {
+      position[axis].clamp(map_min[axis] + half_view[axis], map_max[axis] - half_view[axis])
+    };
+  }
+  transform.translation = position.extend(transform.translation.z);
+}
+
+fn reset_camera_zoom(mut camera: Query<&mut OrthographicProjection, With<MainCamera>>) {
+  for mut projection in camera.iter_mut() {
+    projection.scale = 1.;
+  }
+}
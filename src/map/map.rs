@@ -1,33 +1,61 @@
 use bevy::prelude::*;
 use bevy::reflect::TypeUuid;
 use bevy::render::camera::ScalingMode;
+use bevy_ecs_tilemap::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::gameplay_ui::*;
 use crate::movement::*;
-use crate::{Enemy, GameAssets, GameData, GameState, Path};
+use crate::{
+  despawn_enemy_on_death, Enemy, EnemyLeakedEvent, EnemyTraits, EnemyType, EnemyTypeStats,
+  GameAssets, GameData, GameState, Path, PathCurve, SimulationSet, StatusEffects,
+};
 
 pub struct MapPlugin;
 
 impl Plugin for MapPlugin {
   fn build(&self, app: &mut App) {
     app
+      .add_system(setup_camera.in_schedule(OnExit(GameState::AssetLoading)))
       .add_systems(
-        (load_map, setup_camera.after(load_map)).in_schedule(OnExit(GameState::AssetLoading)),
+        (load_map, center_camera.after(load_map)).in_schedule(OnExit(GameState::LevelLoading)),
       )
+      .add_event::<ChangeTileEvent>()
       .add_system(render_map.in_schedule(OnEnter(GameState::Gameplay)))
-      .add_systems((update_enemy_checkpoint, despawn_enemy).in_set(OnUpdate(GameState::Gameplay)));
+      .add_system(change_tiles.in_set(OnUpdate(GameState::Gameplay)))
+      .add_system(
+        update_enemy_checkpoint
+          .after(basic_movement)
+          .in_set(SimulationSet::Movement)
+          .in_schedule(CoreSchedule::FixedUpdate),
+      )
+      .add_system(
+        despawn_enemy
+          .after(despawn_enemy_on_death)
+          .in_set(SimulationSet::Cleanup)
+          .in_schedule(CoreSchedule::FixedUpdate),
+      );
   }
 }
 
-#[derive(Resource)]
+// Route enemies walk along, from a spawn to an end
+#[derive(Debug, Clone)]
 pub struct MapPath {
+  pub name: String,
   pub checkpoints: Vec<Vec3>,
+  // Curve through the checkpoints, when the map has smooth paths
+  pub curve: Option<PathCurve>,
 }
 
 #[derive(Component)]
 pub struct TileMap;
 
+// Change the tile at a coordinate of the loaded map, in world order
+pub struct ChangeTileEvent {
+  pub coordinate: Point,
+  pub tile: Tile,
+}
+
 #[derive(Default, Debug)]
 pub struct Point {
   pub x: usize,
@@ -50,59 +78,168 @@ impl Point {
   pub fn to_vec3(&self) -> Vec3 {
     Vec3::new(self.x as f32, self.y as f32, 0.)
   }
+
+  // Horizontally or vertically adjacent
+  pub fn is_next_to(&self, other: &Point) -> bool {
+    self.x.abs_diff(other.x) + self.y.abs_diff(other.y) == 1
+  }
 }
 
-#[derive(Resource, Serialize, Deserialize, TypeUuid)]
+#[derive(Resource, Clone, Serialize, Deserialize, TypeUuid)]
 #[uuid = "58d181c2-39f7-4ac7-8ae7-b3cee0667ce2"]
 pub struct Map {
   pub width: usize,
   pub height: usize,
   pub tiles: Vec<Vec<Tile>>,
   pub tile_size: usize,
-  pub checkpoints: Vec<Vec3>,
+  // Names of the paths, by the index used in `Tile::Path`. Unnamed paths are called "Path <index>"
+  #[serde(default)]
+  pub path_names: Vec<String>,
+  // Enemies walk rounded corners instead of turning on the tile centres
+  #[serde(default)]
+  pub smooth_paths: bool,
+  // Built from the tiles when the map is loaded, one per path index that
+  // has a spawn and an end, so every path has checkpoints
+  #[serde(skip)]
+  pub paths: Vec<MapPath>,
 }
 
 fn load_map(game_data: Res<GameData>, mut map: ResMut<Assets<Map>>) {
   let Some(map) = map.get_mut(&game_data.map)
     else { return; };
-
-  let mut path_tiles = vec![];
-  let mut spawn: Point = Default::default();
-  let mut end: Point = Default::default();
+  // The asset stays loaded when the same level is played again, it's already flipped
+  if !map.paths.is_empty() {
+    return;
+  }
 
   map.tiles.reverse();
+  map.create_paths();
+}
 
-  for (y, row) in map.tiles.iter().enumerate() {
-    for (x, tile) in row.iter().enumerate() {
-      match tile {
-        Tile::Spawn => spawn = Point { x, y },
-        Tile::Path(_) => path_tiles.push(Point { x, y }),
-        Tile::End => end = Point { x, y },
-        _ => {}
-      }
+impl Map {
+  // Change the size of the map in file order. Rows are added or removed at the top
+  // and columns at the right, so the rest of the map stays in place
+  pub fn resize(&mut self, width: usize, height: usize) {
+    let width = width.max(1);
+    let height = height.max(1);
+
+    while self.tiles.len() < height {
+      self.tiles.insert(0, vec![Tile::Grass; width]);
     }
+    while self.tiles.len() > height {
+      self.tiles.remove(0);
+    }
+    for row in self.tiles.iter_mut() {
+      row.resize(width, Tile::Grass);
+    }
+
+    self.width = width;
+    self.height = height;
   }
 
-  map.checkpoints.push(spawn.to_vec3());
-  map.create_checkpoints(path_tiles, spawn, end);
-}
+  // Tile under a world position, once the map is loaded
+  pub fn tile_at(&self, position: Vec3) -> Option<&Tile> {
+    let tile_size = self.tile_size as f32;
+    // Tiles are centered on their position
+    let column = ((position.x + tile_size / 2.) / tile_size).floor();
+    let row = ((position.y + tile_size / 2.) / tile_size).floor();
+    if column < 0. || row < 0. {
+      return None;
+    }
 
-impl Map {
-  fn create_checkpoints(&mut self, mut path_tiles: Vec<Point>, spawn: Point, end: Point) {
-    let mut last_point = spawn;
+    self.tiles.get(row as usize)?.get(column as usize)
+  }
+
+  // Index of the path with the given name
+  pub fn path_index(&self, name: &str) -> Option<usize> {
+    self.paths.iter().position(|path| path.name == name)
+  }
+
+  // Path for enemies without one, taking turns on the map's paths
+  pub fn route_for(&self, spawn_index: usize) -> Option<usize> {
+    (!self.paths.is_empty()).then(|| spawn_index % self.paths.len())
+  }
+
+  pub fn path_name(&self, index: usize) -> String {
+    self
+      .path_names
+      .get(index)
+      .cloned()
+      .unwrap_or_else(|| format!("Path {}", index))
+  }
+
+  // Build a path for every index used by `Tile::Path`. A path tile can belong to several paths,
+  // and every path starts at the spawn and ends at the end tile next to its path tiles
+  pub fn create_paths(&mut self) {
+    let mut spawns = vec![];
+    let mut ends = vec![];
+    let mut path_tiles: Vec<Vec<Point>> = vec![];
+
+    for (y, row) in self.tiles.iter().enumerate() {
+      for (x, tile) in row.iter().enumerate() {
+        match tile {
+          Tile::Spawn => spawns.push(Point { x, y }),
+          Tile::End => ends.push(Point { x, y }),
+          Tile::Path(indices) => {
+            for &index in indices {
+              if path_tiles.len() <= index {
+                path_tiles.resize_with(index + 1, Vec::new);
+              }
+              path_tiles[index].push(Point { x, y });
+            }
+          }
+          _ => {}
+        }
+      }
+    }
+
+    self.paths = path_tiles
+      .into_iter()
+      .enumerate()
+      // Path indices that aren't used by any tile
+      .filter(|(_, tiles)| !tiles.is_empty())
+      .filter_map(|(index, tiles)| {
+        let spawn = spawns
+          .iter()
+          .find(|spawn| tiles.iter().any(|tile| spawn.is_next_to(tile)));
+        let end = ends
+          .iter()
+          .find(|end| tiles.iter().any(|tile| end.is_next_to(tile)));
+
+        let (Some(spawn), Some(end)) = (spawn, end)
+          else {
+            error!("{} has no spawn or end next to it", self.path_name(index));
+            return None;
+          };
+        let checkpoints = self.create_checkpoints(tiles, spawn, end);
+
+        let curve = self
+          .smooth_paths
+          .then(|| PathCurve::from_checkpoints(&checkpoints, self.tile_size as f32 / 2.));
+
+        Some(MapPath {
+          name: self.path_name(index),
+          checkpoints,
+          curve,
+        })
+      })
+      .collect();
+  }
+
+  fn create_checkpoints(&self, mut path_tiles: Vec<Point>, spawn: &Point, end: &Point) -> Vec<Vec3> {
+    let mut checkpoints = vec![spawn.to_vec3() * self.tile_size as f32];
+    let mut last_point = Point {
+      x: spawn.x,
+      y: spawn.y,
+    };
 
     loop {
-      let next_point_position = path_tiles.iter().position(|point| {
-        let length = Vec2::new(
-          last_point.x as f32 - point.x as f32,
-          last_point.y as f32 - point.y as f32,
-        )
-        .length();
-        (0.9..=1.1).contains(&length)
-      });
+      let next_point_position = path_tiles
+        .iter()
+        .position(|point| last_point.is_next_to(point));
       if let Some(next_point_position) = next_point_position {
         let next_point = path_tiles.remove(next_point_position);
-        self.checkpoints.push(
+        checkpoints.push(
           Coordinate {
             x: (next_point.x * self.tile_size) as f32,
             y: (next_point.y * self.tile_size + self.tile_size / 2) as f32,
@@ -111,20 +248,15 @@ impl Map {
         );
         last_point = next_point;
       } else {
-        self.checkpoints.push(
+        checkpoints.push(
           Coordinate {
             x: (end.x * self.tile_size) as f32,
             y: (end.y * self.tile_size + self.tile_size / 2) as f32,
           }
           .to_vec3(),
         );
-        self.checkpoints[0] = Coordinate {
-          x: self.checkpoints[0].x * self.tile_size as f32,
-          y: self.checkpoints[0].y * self.tile_size as f32,
-        }
-        .to_vec3();
 
-        return;
+        return checkpoints;
       }
     }
   }
@@ -134,12 +266,8 @@ impl Map {
 #[derive(Component)]
 pub struct MainCamera;
 
-fn setup_camera(mut commands: Commands, game_data: Res<GameData>, map: Res<Assets<Map>>) {
-  let Some(map) = map.get(&game_data.map)
-    else { return; };
+fn setup_camera(mut commands: Commands) {
   let mut camera = Camera2dBundle::default();
-  camera.transform.translation.x = (map.width as f32 / 2. - 0.5) * map.tile_size as f32;
-  camera.transform.translation.y = (map.height as f32 / 2. - 0.5) * map.tile_size as f32;
   camera.projection.scaling_mode = ScalingMode::AutoMin {
     min_width: 1280.,
     min_height: 720.0,
@@ -147,6 +275,23 @@ fn setup_camera(mut commands: Commands, game_data: Res<GameData>, map: Res<Asset
   commands.spawn((camera, MainCamera));
 }
 
+// Look at the middle of the level's map
+fn center_camera(
+  game_data: Res<GameData>,
+  map: Res<Assets<Map>>,
+  mut camera: Query<&mut Transform, With<MainCamera>>,
+) {
+  let Some(map) = map.get(&game_data.map)
+    else { return; };
+
+  for mut transform in camera.iter_mut() {
+    transform.translation.x = (map.width as f32 / 2. - 0.5) * map.tile_size as f32;
+    transform.translation.y = (map.height as f32 / 2. - 0.5) * map.tile_size as f32;
+  }
+}
+
+// One tilemap for the whole map. Every tile is still an entity with a `MapTile`,
+// found through the tilemap's `TileStorage`
 fn render_map(
   mut commands: Commands,
   game_data: Res<GameData>,
@@ -156,34 +301,103 @@ fn render_map(
   let Some(map) = map.get(&game_data.map)
     else { return; };
 
+  let size = TilemapSize {
+    x: map.width as u32,
+    y: map.height as u32,
+  };
+  let tile_size = TilemapTileSize {
+    x: map.tile_size as f32,
+    y: map.tile_size as f32,
+  };
+  let tilemap = commands.spawn_empty().id();
+  let mut storage = TileStorage::empty(size);
+
+  for row in 0..map.height {
+    for column in 0..map.width {
+      let tile = &map.tiles[row][column];
+      let position = TilePos {
+        x: column as u32,
+        y: row as u32,
+      };
+      let tile_entity = commands
+        .spawn(TileBundle {
+          position,
+          texture_index: TileTextureIndex(tile.texture_index()),
+          tilemap_id: TilemapId(tilemap),
+          ..default()
+        })
+        .insert(MapTile {
+          coordinate: Point { x: column, y: row },
+          tile: tile.clone(),
+        })
+        .insert(Name::new(format!("{:?}", tile)))
+        .id();
+      commands.entity(tilemap).add_child(tile_entity);
+      storage.set(&position, tile_entity);
+    }
+  }
+
   commands
-    .spawn(SpatialBundle::default())
-    .with_children(|commands| {
-      for row in 0..map.height {
-        for column in 0..map.width {
-          let tile = &map.tiles[row][column];
-          commands
-            .spawn(SpriteBundle {
-              texture: assets.get_tile(tile).clone(),
-              transform: Transform::from_translation(Vec3::new(
-                column as f32 * map.tile_size as f32,
-                row as f32 * map.tile_size as f32,
-                -0.000000000000001,
-              )),
-              ..Default::default()
-            })
-            .insert(MapTile {
-              coordinate: Point { x: column, y: row },
-              tile: tile.clone(),
-            })
-            .insert(Name::new(format!("{:?}", tile)));
-        }
-      }
+    .entity(tilemap)
+    .insert(TilemapBundle {
+      grid_size: tile_size.into(),
+      map_type: TilemapType::Square,
+      size,
+      storage,
+      texture: TilemapTexture::Vector(assets.tile_textures()),
+      tile_size,
+      // Tiles are centered on their position, like sprites
+      transform: Transform::from_xyz(0., 0., -0.000000000000001),
+      ..default()
     })
     .insert(TileMap)
     .insert(Name::new("TileMap"));
 }
 
+// Replace map tiles while the game is running. Routes are rebuilt when a path tile changes
+fn change_tiles(
+  mut change_events: EventReader<ChangeTileEvent>,
+  game_data: Res<GameData>,
+  mut maps: ResMut<Assets<Map>>,
+  tilemap: Query<&TileStorage, With<TileMap>>,
+  mut tiles: Query<(&mut MapTile, &mut TileTextureIndex, &mut Name)>,
+) {
+  let Some(map) = maps.get_mut(&game_data.map)
+    else { return; };
+  let Ok(storage) = tilemap.get_single()
+    else { return; };
+
+  let mut paths_changed = false;
+  for event in change_events.iter() {
+    let Point { x, y } = event.coordinate;
+    let Some(map_tile) = map.tiles.get_mut(y).and_then(|row| row.get_mut(x))
+      else {
+        warn!("Tile ({}, {}) is outside of the map", x, y);
+        continue;
+      };
+
+    paths_changed |= map_tile.is_route() || event.tile.is_route();
+    *map_tile = event.tile.clone();
+
+    let position = TilePos {
+      x: x as u32,
+      y: y as u32,
+    };
+    let Some((mut tile, mut texture_index, mut name)) = storage
+      .get(&position)
+      .and_then(|entity| tiles.get_mut(entity).ok())
+      else { continue; };
+
+    tile.tile = event.tile.clone();
+    texture_index.0 = event.tile.texture_index();
+    *name = Name::new(format!("{:?}", event.tile));
+  }
+
+  if paths_changed {
+    map.create_paths();
+  }
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Tile {
   Grass,
@@ -194,6 +408,23 @@ pub enum Tile {
   Empty,
 }
 
+impl Tile {
+  // Index in `GameAssets::tile_textures`
+  pub fn texture_index(&self) -> u32 {
+    match self {
+      Tile::Water => 1,
+      Tile::Path(_) | Tile::Spawn | Tile::End => 2,
+      _ => 0,
+    }
+  }
+
+  // Part of an enemy route
+  pub fn is_route(&self) -> bool {
+    matches!(self, Tile::Path(_) | Tile::Spawn | Tile::End)
+  }
+}
+
+// Tile of the rendered map. Coordinates are in world order, row 0 is the bottom row
 #[derive(Component)]
 pub struct MapTile {
   pub coordinate: Point,
@@ -202,52 +433,89 @@ pub struct MapTile {
 
 fn despawn_enemy(
   mut commands: Commands,
-  mut enemies: Query<(Entity, &Enemy, &mut Path)>,
+  mut enemies: Query<(Entity, &Enemy, &EnemyType, &mut Path)>,
   mut base: Query<&mut Base>,
+  mut leak_event_writer: EventWriter<EnemyLeakedEvent>,
   game_data: Res<GameData>,
   map: Res<Assets<Map>>,
+  enemy_stats: Res<Assets<EnemyTypeStats>>,
 ) {
   let Some(map) = map.get(&game_data.map)
     else { return; };
+  let Some(enemy_stats) = enemy_stats.get(&game_data.enemy_type_stats)
+    else { return; };
 
   let mut base = base.single_mut();
 
-  for (entity, enemy, path) in &mut enemies {
-    if path.index >= map.checkpoints.len() {
-      damage_base(&mut commands, &entity, enemy.health, &mut base);
+  for (entity, enemy, enemy_type, path) in &mut enemies {
+    // Editing the tiles can remove the enemy's path
+    let Some(route) = map.paths.get(path.route)
+      else {
+        warn!("Enemy on missing path {}, removing it", path.route);
+        commands.entity(entity).despawn_recursive();
+        continue;
+      };
+
+    // Enemies killed on the last tile died, they didn't leak
+    if path.index >= route.checkpoints.len() && enemy.health > 0 {
+      let damage = enemy_stats.leak_damage(*enemy_type);
+      damage_base(&mut commands, &entity, damage, &mut base);
+      leak_event_writer.send(EnemyLeakedEvent {
+        enemy_type: *enemy_type,
+        damage,
+      });
     }
   }
 }
 
 fn update_enemy_checkpoint(
-  mut enemies: Query<(&mut Movement, &mut Transform, &mut Path)>,
+  mut enemies: Query<(&mut Movement, &mut Transform, &mut Path, &StatusEffects, &EnemyTraits)>,
   game_data: Res<GameData>,
   map: Res<Assets<Map>>,
-  time: Res<Time>,
+  fixed_time: Res<FixedTime>,
 ) {
   let Some(map) = map.get(&game_data.map)
     else { return; };
 
-  for (mut movement, mut transform, mut path) in &mut enemies {
-    if path.index >= map.checkpoints.len() {
+  for (mut movement, mut transform, mut path, status_effects, traits) in &mut enemies {
+    // Slowed enemies move a part of their speed, speed bursts move faster
+    let speed = movement.speed * status_effects.speed_multiplier() * traits.speed_multiplier();
+
+    // Removed by despawn_enemy
+    let Some(route) = map.paths.get(path.route)
+      else { continue; };
+    let checkpoints = &route.checkpoints;
+    if path.index >= checkpoints.len() {
+      continue;
+    }
+
+    // Smooth paths place the enemy by how far it has travelled along the curve
+    if let Some(curve) = &route.curve {
+      movement.distance_travelled += speed * fixed_time.period.as_secs_f32();
+      let (position, direction) = curve.position_at(movement.distance_travelled);
+      transform.translation = position;
+      movement.direction = direction;
+      if movement.distance_travelled >= curve.length() {
+        path.index = checkpoints.len();
+      }
       continue;
     }
 
-    let distance = map.checkpoints[path.index] - transform.translation;
+    let distance = checkpoints[path.index] - transform.translation;
     if distance == Vec3::ZERO {
       path.index += 1;
       continue;
     }
-    let enemy_movement = distance.normalize() * movement.speed * time.delta_seconds();
+    let enemy_movement = distance.normalize() * speed * fixed_time.period.as_secs_f32();
 
     if enemy_movement.length() > distance.length() {
-      transform.translation = map.checkpoints[path.index];
+      transform.translation = checkpoints[path.index];
       movement.distance_travelled += distance.length();
-      movement.direction = map.checkpoints[path.index] - transform.translation;
+      movement.direction = checkpoints[path.index] - transform.translation;
       path.index += 1;
     } else {
       movement.distance_travelled += enemy_movement.length();
-      movement.direction = map.checkpoints[path.index] - transform.translation;
+      movement.direction = checkpoints[path.index] - transform.translation;
       transform.translation += enemy_movement;
     }
   }
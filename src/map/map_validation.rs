@@ -0,0 +1,493 @@
+use bevy::prelude::*;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::map::*;
+
+// Problem found in a map file. Tile coordinates are (column, row), with row 0 being
+// the first row of `tiles` in the file
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapError {
+  NoPaths,
+  HeightMismatch { height: usize, rows: usize },
+  WidthMismatch { row: usize, width: usize, columns: usize },
+  MissingSpawn { path: String },
+  DuplicateSpawn { path: String, spawns: Vec<(usize, usize)> },
+  OrphanSpawn { spawn: (usize, usize) },
+  MissingEnd { path: String },
+  EndNotReached { path: String, last_tile: (usize, usize) },
+  AmbiguousBranch { path: String, tile: (usize, usize), next_tiles: Vec<(usize, usize)> },
+  Loop { path: String, tile: (usize, usize) },
+  DiagonalTouch { path: String, tiles: ((usize, usize), (usize, usize)) },
+  DisconnectedTiles { path: String, tiles: Vec<(usize, usize)> },
+}
+
+impl fmt::Display for MapError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      MapError::NoPaths => write!(f, "there are no path tiles"),
+      MapError::HeightMismatch { height, rows } => {
+        write!(f, "height is {} but there are {} rows of tiles", height, rows)
+      }
+      MapError::WidthMismatch {
+        row,
+        width,
+        columns,
+      } => write!(f, "width is {} but row {} has {} tiles", width, row, columns),
+      MapError::MissingSpawn { path } => write!(f, "{} has no spawn next to it", path),
+      MapError::DuplicateSpawn { path, spawns } => {
+        write!(f, "{} has several spawns next to it at {:?}", path, spawns)
+      }
+      MapError::OrphanSpawn { spawn } => write!(f, "spawn at {:?} isn't next to a path", spawn),
+      MapError::MissingEnd { path } => write!(f, "{} has no end next to it", path),
+      MapError::EndNotReached { path, last_tile } => write!(
+        f,
+        "{} stops at {:?}, which isn't next to an end",
+        path, last_tile
+      ),
+      MapError::AmbiguousBranch {
+        path,
+        tile,
+        next_tiles,
+      } => write!(
+        f,
+        "{} branches at {:?}, it can continue to any of {:?}",
+        path, tile, next_tiles
+      ),
+      MapError::Loop { path, tile } => write!(
+        f,
+        "{} loops back on itself, both ways from {:?} meet again",
+        path, tile
+      ),
+      MapError::DiagonalTouch { path, tiles } => write!(
+        f,
+        "{} touches itself diagonally at {:?} and {:?}",
+        path, tiles.0, tiles.1
+      ),
+      MapError::DisconnectedTiles { path, tiles } => write!(
+        f,
+        "{} has tiles that can't be reached from its spawn at {:?}",
+        path, tiles
+      ),
+    }
+  }
+}
+
+impl Map {
+  // Check the map as written in the file, before the tiles are flipped on load.
+  // Walks every path the same way the checkpoints are created and reports where it goes wrong
+  pub fn validate(&self) -> Vec<MapError> {
+    let mut errors = vec![];
+
+    if self.tiles.len() != self.height {
+      errors.push(MapError::HeightMismatch {
+        height: self.height,
+        rows: self.tiles.len(),
+      });
+    }
+    for (row, tiles) in self.tiles.iter().enumerate() {
+      if tiles.len() != self.width {
+        errors.push(MapError::WidthMismatch {
+          row,
+          width: self.width,
+          columns: tiles.len(),
+        });
+      }
+    }
+
+    let mut spawns = vec![];
+    let mut ends = vec![];
+    let mut path_tiles: Vec<Vec<Point>> = vec![];
+
+    for (y, row) in self.tiles.iter().enumerate() {
+      for (x, tile) in row.iter().enumerate() {
+        match tile {
+          Tile::Spawn => spawns.push(Point { x, y }),
+          Tile::End => ends.push(Point { x, y }),
+          Tile::Path(indices) => {
+            for &index in indices {
+              if path_tiles.len() <= index {
+                path_tiles.resize_with(index + 1, Vec::new);
+              }
+              path_tiles[index].push(Point { x, y });
+            }
+          }
+          _ => {}
+        }
+      }
+    }
+
+    if path_tiles.iter().all(|tiles| tiles.is_empty()) {
+      errors.push(MapError::NoPaths);
+    }
+    for spawn in spawns.iter() {
+      if !path_tiles.iter().flatten().any(|tile| spawn.is_next_to(tile)) {
+        errors.push(MapError::OrphanSpawn {
+          spawn: (spawn.x, spawn.y),
+        });
+      }
+    }
+
+    for (index, mut tiles) in path_tiles.into_iter().enumerate() {
+      // Path indices that no tile uses
+      if tiles.is_empty() {
+        continue;
+      }
+      let path = self.path_name(index);
+
+      let path_spawns = spawns
+        .iter()
+        .filter(|spawn| tiles.iter().any(|tile| spawn.is_next_to(tile)))
+        .collect::<Vec<_>>();
+      let has_end = ends
+        .iter()
+        .any(|end| tiles.iter().any(|tile| end.is_next_to(tile)));
+
+      if !has_end {
+        errors.push(MapError::MissingEnd { path: path.clone() });
+      }
+      let spawn = match path_spawns[..] {
+        [] => {
+          errors.push(MapError::MissingSpawn { path });
+          continue;
+        }
+        [spawn] => spawn,
+        _ => {
+          errors.push(MapError::DuplicateSpawn {
+            path,
+            spawns: path_spawns.iter().map(|spawn| (spawn.x, spawn.y)).collect(),
+          });
+          continue;
+        }
+      };
+
+      // Follow the path from the spawn, every tile must have exactly one way forward
+      let mut last_point = Point {
+        x: spawn.x,
+        y: spawn.y,
+      };
+      // Spawn and tiles in the order they are walked
+      let mut route = vec![(spawn.x, spawn.y)];
+      loop {
+        let next_tiles = tiles
+          .iter()
+          .filter(|point| last_point.is_next_to(point))
+          .map(|point| (point.x, point.y))
+          .collect::<Vec<_>>();
+
+        if next_tiles.len() > 1 {
+          // Ways forward that meet again further on are a loop
+          let tile = (last_point.x, last_point.y);
+          errors.push(if connected(&tiles, next_tiles[0], next_tiles[1]) {
+            MapError::Loop {
+              path: path.clone(),
+              tile,
+            }
+          } else {
+            MapError::AmbiguousBranch {
+              path: path.clone(),
+              tile,
+              next_tiles,
+            }
+          });
+          break;
+        }
+
+        let Some(next_point_position) = tiles
+          .iter()
+          .position(|point| last_point.is_next_to(point))
+          else {
+            if has_end && !ends.iter().any(|end| end.is_next_to(&last_point)) {
+              errors.push(MapError::EndNotReached {
+                path: path.clone(),
+                last_tile: (last_point.x, last_point.y),
+              });
+            }
+            break;
+          };
+        last_point = tiles.remove(next_point_position);
+        route.push((last_point.x, last_point.y));
+      }
+
+      // Anything left over isn't on the route from the spawn. Not reported after a branch,
+      // since the other side of the branch is left over as well
+      let branched = matches!(
+        errors.last(),
+        Some(MapError::AmbiguousBranch { .. } | MapError::Loop { .. })
+      );
+      if !tiles.is_empty() && !branched {
+        errors.push(MapError::DisconnectedTiles {
+          path: path.clone(),
+          tiles: tiles.iter().map(|point| (point.x, point.y)).collect(),
+        });
+      }
+
+      // Two tiles after each other form a corner, further apart they mustn't touch
+      let touch = route.iter().enumerate().find_map(|(index, &tile)| {
+        let other = route[(index + 3).min(route.len())..]
+          .iter()
+          .find(|other| tile.0.abs_diff(other.0) == 1 && tile.1.abs_diff(other.1) == 1)?;
+        Some((tile, *other))
+      });
+      if let Some(tiles) = touch {
+        errors.push(MapError::DiagonalTouch { path, tiles });
+      }
+    }
+
+    errors
+  }
+}
+
+// Whether two tiles are joined by the other tiles, without going back over the walked ones
+fn connected(tiles: &[Point], from: (usize, usize), to: (usize, usize)) -> bool {
+  let mut reached = vec![from];
+  let mut next = vec![from];
+  while let Some((x, y)) = next.pop() {
+    if (x, y) == to {
+      return true;
+    }
+    for tile in tiles {
+      let tile = (tile.x, tile.y);
+      if x.abs_diff(tile.0) + y.abs_diff(tile.1) == 1 && !reached.contains(&tile) {
+        reached.push(tile);
+        next.push(tile);
+      }
+    }
+  }
+
+  false
+}
+
+// Log every problem with a map, returns true when there are none
+pub fn report_map_errors(name: &str, map: &Map) -> bool {
+  let errors = map.validate();
+  for err in errors.iter() {
+    error!("MAP ERROR in {}: {}", name, err);
+  }
+
+  errors.is_empty()
+}
+
+// Read the map path from `--check-map <file>`
+pub fn check_map_path_from_args() -> Option<String> {
+  let args: Vec<String> = std::env::args().collect();
+  let index = args.iter().position(|arg| arg == "--check-map")?;
+
+  args.get(index + 1).cloned()
+}
+
+// Read a .map.ron or Tiled map from disk, with the loader the game would use for it
+pub fn read_map_file(path: &str) -> Result<Map, String> {
+  if is_tiled_map(Path::new(path)) {
+    return read_tiled_map(Path::new(path)).map_err(|err| err.to_string());
+  }
+
+  fs::read_to_string(path)
+    .map_err(|err| err.to_string())
+    .and_then(|contents| ron::from_str(&contents).map_err(|err| err.to_string()))
+}
+
+// Validate a map file without starting the game, returns the process exit code
+pub fn check_map_file(path: &str) -> i32 {
+  let map = match read_map_file(path) {
+    Ok(map) => map,
+    Err(err) => {
+      println!("Could not read map {}: {}", path, err);
+      return 2;
+    }
+  };
+
+  let errors = map.validate();
+  for err in errors.iter() {
+    println!("{}: {}", path, err);
+  }
+
+  if errors.is_empty() {
+    println!("{}: OK", path);
+    0
+  } else {
+    1
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // One character per tile: S spawn, E end, a digit is a path tile of that index, anything
+  // else is grass
+  fn map(rows: &[&str]) -> Map {
+    let tiles = rows
+      .iter()
+      .map(|row| {
+        row
+          .chars()
+          .map(|tile| match tile {
+            'S' => Tile::Spawn,
+            'E' => Tile::End,
+            '0'..='9' => Tile::Path(vec![tile.to_digit(10).unwrap() as usize]),
+            _ => Tile::Grass,
+          })
+          .collect::<Vec<_>>()
+      })
+      .collect::<Vec<_>>();
+
+    Map {
+      width: tiles[0].len(),
+      height: tiles.len(),
+      tiles,
+      tile_size: 80,
+      path_names: vec![],
+      smooth_paths: false,
+      paths: vec![],
+    }
+  }
+
+  fn path() -> String {
+    "Path 0".to_string()
+  }
+
+  #[test]
+  fn valid_map() {
+    assert_eq!(map(&["S00", "..0", "..E"]).validate(), vec![]);
+  }
+
+  #[test]
+  fn shipped_maps_are_valid() {
+    for contents in [
+      include_str!("../../assets/data/level1.map.ron"),
+      include_str!("../../assets/data/level2.map.ron"),
+    ] {
+      let map: Map = ron::from_str(contents).unwrap();
+      assert_eq!(map.validate(), vec![]);
+    }
+  }
+
+  #[test]
+  fn no_paths() {
+    assert_eq!(map(&["..E"]).validate(), vec![MapError::NoPaths]);
+    assert_eq!(
+      map(&["S.E"]).validate(),
+      vec![MapError::NoPaths, MapError::OrphanSpawn { spawn: (0, 0) }]
+    );
+  }
+
+  #[test]
+  fn height_mismatch() {
+    let mut map = map(&["S0E"]);
+    map.height = 2;
+    assert_eq!(
+      map.validate(),
+      vec![MapError::HeightMismatch { height: 2, rows: 1 }]
+    );
+  }
+
+  #[test]
+  fn width_mismatch() {
+    assert_eq!(
+      map(&["S0E", "."]).validate(),
+      vec![MapError::WidthMismatch {
+        row: 1,
+        width: 3,
+        columns: 1
+      }]
+    );
+  }
+
+  #[test]
+  fn missing_spawn() {
+    assert_eq!(
+      map(&[".0E"]).validate(),
+      vec![MapError::MissingSpawn { path: path() }]
+    );
+  }
+
+  #[test]
+  fn duplicate_spawn() {
+    assert_eq!(
+      map(&["S0S", ".E."]).validate(),
+      vec![MapError::DuplicateSpawn {
+        path: path(),
+        spawns: vec![(0, 0), (2, 0)]
+      }]
+    );
+  }
+
+  #[test]
+  fn orphan_spawn() {
+    assert_eq!(
+      map(&["S0E", "...", "..S"]).validate(),
+      vec![MapError::OrphanSpawn { spawn: (2, 2) }]
+    );
+  }
+
+  #[test]
+  fn missing_end() {
+    assert_eq!(
+      map(&["S00"]).validate(),
+      vec![MapError::MissingEnd { path: path() }]
+    );
+  }
+
+  #[test]
+  fn end_not_reached() {
+    assert_eq!(
+      map(&["S000", ".E.."]).validate(),
+      vec![MapError::EndNotReached {
+        path: path(),
+        last_tile: (3, 0)
+      }]
+    );
+  }
+
+  #[test]
+  fn ambiguous_branch() {
+    assert_eq!(
+      map(&["S00", ".0.", ".E."]).validate(),
+      vec![MapError::AmbiguousBranch {
+        path: path(),
+        tile: (1, 0),
+        next_tiles: vec![(2, 0), (1, 1)]
+      }]
+    );
+  }
+
+  #[test]
+  fn path_loop() {
+    assert_eq!(
+      map(&["S00.", ".00.", ".E.."]).validate(),
+      vec![MapError::Loop {
+        path: path(),
+        tile: (1, 0)
+      }]
+    );
+  }
+
+  #[test]
+  fn diagonal_touch() {
+    assert_eq!(
+      map(&[".S...", "00...", "0.00E", "000.."]).validate(),
+      vec![MapError::DiagonalTouch {
+        path: path(),
+        tiles: ((1, 1), (2, 2))
+      }]
+    );
+  }
+
+  #[test]
+  fn disconnected_tiles() {
+    assert_eq!(
+      map(&["S0E", "...", "0.."]).validate(),
+      vec![MapError::DisconnectedTiles {
+        path: path(),
+        tiles: vec![(0, 2)]
+      }]
+    );
+  }
+
+  #[test]
+  fn unused_path_indices_are_skipped() {
+    assert_eq!(map(&["S1E"]).validate(), vec![]);
+  }
+}
@@ -0,0 +1,176 @@
+use bevy::prelude::*;
+
+// Points sampled on each rounded corner
+const CORNER_SEGMENTS: usize = 8;
+
+// Route with rounded corners, sampled into short straight pieces.
+// Positions are looked up by the distance along the curve
+#[derive(Debug, Clone)]
+pub struct PathCurve {
+  points: Vec<Vec3>,
+  // Distance along the curve to every point
+  distances: Vec<f32>,
+}
+
+impl PathCurve {
+  // Round every corner of the checkpoints with a quadratic curve of the given radius
+  pub fn from_checkpoints(checkpoints: &[Vec3], radius: f32) -> Self {
+    let mut points = vec![];
+
+    for (index, &checkpoint) in checkpoints.iter().enumerate() {
+      let (Some(&previous), Some(&next)) = (
+        index.checked_sub(1).and_then(|index| checkpoints.get(index)),
+        checkpoints.get(index + 1),
+      )
+        else {
+          points.push(checkpoint);
+          continue;
+        };
+
+      let to_previous = previous - checkpoint;
+      let to_next = next - checkpoint;
+      // Straight through, no corner to round
+      if to_previous.length() == 0.
+        || to_next.length() == 0.
+        || to_previous.normalize().cross(to_next.normalize()).length() < 0.001
+      {
+        points.push(checkpoint);
+        continue;
+      }
+
+      // Corners can't reach past the middle of the straight parts next to them
+      let radius = radius
+        .min(to_previous.length() / 2.)
+        .min(to_next.length() / 2.);
+      let start = checkpoint + to_previous.normalize() * radius;
+      let end = checkpoint + to_next.normalize() * radius;
+
+      for step in 0..=CORNER_SEGMENTS {
+        let t = step as f32 / CORNER_SEGMENTS as f32;
+        points.push(
+          start * (1. - t) * (1. - t) + checkpoint * 2. * (1. - t) * t + end * t * t,
+        );
+      }
+    }
+
+    let mut distances = Vec::with_capacity(points.len());
+    let mut distance = 0.;
+    for (index, point) in points.iter().enumerate() {
+      if index > 0 {
+        distance += point.distance(points[index - 1]);
+      }
+      distances.push(distance);
+    }
+
+    Self { points, distances }
+  }
+
+  pub fn length(&self) -> f32 {
+    self.distances.last().copied().unwrap_or(0.)
+  }
+
+  // Position and direction at a distance along the curve
+  pub fn position_at(&self, distance: f32) -> (Vec3, Vec3) {
+    let Some(&last) = self.points.last()
+      else { return (Vec3::ZERO, Vec3::ZERO); };
+
+    // Index of the first point past the distance
+    let next = self.distances.partition_point(|&point_distance| point_distance <= distance);
+    if next == 0 {
+      let direction = self.points.get(1).map_or(Vec3::ZERO, |&point| point - self.points[0]);
+      return (self.points[0], direction);
+    }
+    if next >= self.points.len() {
+      let direction = last - self.points[self.points.len().saturating_sub(2)];
+      return (last, direction);
+    }
+
+    let (from, to) = (self.points[next - 1], self.points[next]);
+    let segment_length = self.distances[next] - self.distances[next - 1];
+    let t = (distance - self.distances[next - 1]) / segment_length;
+
+    (from.lerp(to, t), to - from)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn polyline_length(checkpoints: &[Vec3]) -> f32 {
+    checkpoints.windows(2).map(|pair| pair[0].distance(pair[1])).sum()
+  }
+
+  fn points_along(curve: &PathCurve, step: f32) -> Vec<Vec3> {
+    let steps = (curve.length() / step).ceil() as usize;
+    (0..=steps)
+      .map(|index| curve.position_at(index as f32 * step).0)
+      .collect()
+  }
+
+  #[test]
+  fn rounded_corners_are_shorter() {
+    let checkpoints = [Vec3::ZERO, Vec3::new(100., 0., 0.), Vec3::new(100., 100., 0.)];
+    let curve = PathCurve::from_checkpoints(&checkpoints, 20.);
+
+    assert!(curve.length() < polyline_length(&checkpoints));
+    // Only the corner is cut, not more than its two straight parts
+    assert!(curve.length() > polyline_length(&checkpoints) - 40.);
+  }
+
+  #[test]
+  fn straight_paths_are_unchanged() {
+    let checkpoints = [Vec3::ZERO, Vec3::new(50., 0., 0.), Vec3::new(120., 0., 0.)];
+    let curve = PathCurve::from_checkpoints(&checkpoints, 20.);
+
+    assert!((curve.length() - 120.).abs() < 1e-4);
+    assert_eq!(curve.position_at(80.).0, Vec3::new(80., 0., 0.));
+  }
+
+  #[test]
+  fn positions_are_continuous_around_corners() {
+    let checkpoints = [
+      Vec3::ZERO,
+      Vec3::new(100., 0., 0.),
+      Vec3::new(100., 100., 0.),
+      Vec3::new(0., 100., 0.),
+    ];
+    let curve = PathCurve::from_checkpoints(&checkpoints, 30.);
+
+    // Moving a little along the curve never jumps
+    let step = 0.5;
+    for pair in points_along(&curve, step).windows(2) {
+      assert!(pair[0].distance(pair[1]) <= step + 1e-3, "{} to {}", pair[0], pair[1]);
+    }
+    for distance in [0., 60., 85., 100., 115., 150., curve.length()] {
+      assert_ne!(curve.position_at(distance).1, Vec3::ZERO, "at {}", distance);
+    }
+
+    // Clamped to the ends
+    assert_eq!(curve.position_at(-10.).0, checkpoints[0]);
+    assert_eq!(curve.position_at(curve.length() + 10.).0, checkpoints[3]);
+  }
+
+  #[test]
+  fn large_radius_is_clamped_to_half_a_segment() {
+    // A short 10 unit step between two long straights
+    let checkpoints = [
+      Vec3::ZERO,
+      Vec3::new(100., 0., 0.),
+      Vec3::new(100., 10., 0.),
+      Vec3::new(200., 10., 0.),
+    ];
+    let curve = PathCurve::from_checkpoints(&checkpoints, 50.);
+
+    // The corners start 5 units from the checkpoint, at the middle of the short step
+    let corner_start = curve.position_at(95.).0;
+    assert!(corner_start.distance(Vec3::new(95., 0., 0.)) < 1e-3, "{}", corner_start);
+
+    // A 50 unit corner would leave the step, the clamped ones stay inside it
+    for point in points_along(&curve, 1.) {
+      assert!((-1e-3..=10. + 1e-3).contains(&point.y), "{}", point);
+      assert!((-1e-3..=200. + 1e-3).contains(&point.x), "{}", point);
+    }
+    assert!(curve.length() < polyline_length(&checkpoints));
+  }
+}
@@ -0,0 +1,881 @@
+use bevy::asset::{AssetLoader, BoxedFuture, Error, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use serde::Deserialize;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use xml::attribute::OwnedAttribute;
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::map::*;
+
+// Tiled stores flip flags in the highest bits of a tile id
+const TILE_ID_MASK: u32 = 0x1fff_ffff;
+
+// Loads maps made in Tiled, from JSON (.tmj) or XML (.tmx).
+// Tiles get their `Tile` from a "tile" property or their class. Point or rectangle objects with
+// the class Spawn/End place spawns and ends, polyline objects with the class Path draw named paths
+pub struct TiledMapLoader;
+
+impl AssetLoader for TiledMapLoader {
+  fn load<'a>(
+    &'a self,
+    bytes: &'a [u8],
+    load_context: &'a mut LoadContext,
+  ) -> BoxedFuture<'a, Result<(), Error>> {
+    Box::pin(async move {
+      let path = load_context.path().to_path_buf();
+      let mut tiled_map = parse_tiled_map(&path, bytes)?;
+
+      // External tilesets are relative to the map file
+      for tileset in tiled_map.tilesets.iter_mut() {
+        let Some(source) = tileset.source.take()
+          else { continue; };
+
+        let tileset_path = path.parent().unwrap_or(Path::new("")).join(&source);
+        let tileset_bytes = load_context.read_asset_bytes(&tileset_path).await?;
+        tileset.tiles = parse_tileset(&tileset_path, &tileset_bytes)?;
+      }
+
+      let map = tiled_map.into_map()?;
+      load_context.set_default_asset(LoadedAsset::new(map));
+      Ok(())
+    })
+  }
+
+  fn extensions(&self) -> &[&str] {
+    &["tmj", "tmx"]
+  }
+}
+
+fn is_xml(path: &Path) -> bool {
+  matches!(path.extension().and_then(|ext| ext.to_str()), Some("tmx" | "tsx"))
+}
+
+pub fn is_tiled_map(path: &Path) -> bool {
+  matches!(path.extension().and_then(|ext| ext.to_str()), Some("tmx" | "tmj"))
+}
+
+// Load a Tiled map straight from disk, the same way as the asset loader
+pub fn read_tiled_map(path: &Path) -> Result<Map, Error> {
+  let mut tiled_map = parse_tiled_map(path, &fs::read(path)?)?;
+
+  for tileset in tiled_map.tilesets.iter_mut() {
+    let Some(source) = tileset.source.take()
+      else { continue; };
+
+    let tileset_path = path.parent().unwrap_or(Path::new("")).join(&source);
+    tileset.tiles = parse_tileset(&tileset_path, &fs::read(&tileset_path)?)?;
+  }
+
+  tiled_map.into_map()
+}
+
+fn parse_tiled_map(path: &Path, bytes: &[u8]) -> Result<TiledMap, Error> {
+  if is_xml(path) {
+    parse_tmx(bytes)
+  } else {
+    serde_json::from_slice::<TmjMap>(bytes)?.into_tiled_map()
+  }
+}
+
+fn parse_tileset(path: &Path, bytes: &[u8]) -> Result<HashMap<u32, Tile>, Error> {
+  if is_xml(path) {
+    parse_tsx(bytes)
+  } else {
+    Ok(serde_json::from_slice::<TmjTileset>(bytes)?.tiles())
+  }
+}
+
+// Tiled map in either format, before it's turned into a `Map`
+#[derive(Default)]
+struct TiledMap {
+  width: usize,
+  height: usize,
+  tile_width: usize,
+  tile_height: usize,
+  // "smooth_paths" map property
+  smooth_paths: bool,
+  tilesets: Vec<TiledTileset>,
+  // Tile ids of every tile layer, row by row from the top
+  layers: Vec<Vec<u32>>,
+  objects: Vec<TiledObject>,
+}
+
+#[derive(Default)]
+struct TiledTileset {
+  first_id: u32,
+  source: Option<String>,
+  // Tile by id within the tileset
+  tiles: HashMap<u32, Tile>,
+}
+
+#[derive(Default)]
+struct TiledObject {
+  name: String,
+  class: String,
+  x: f32,
+  y: f32,
+  width: f32,
+  height: f32,
+  // Points relative to the object's position
+  polyline: Vec<Vec2>,
+}
+
+impl TiledMap {
+  fn into_map(self) -> Result<Map, Error> {
+    if self.tile_width != self.tile_height {
+      return Err(Error::msg(format!(
+        "tiles must be square, they are {}x{}",
+        self.tile_width, self.tile_height
+      )));
+    }
+
+    let mut tiles = vec![vec![Tile::Grass; self.width]; self.height];
+
+    // Later layers are drawn over earlier ones
+    for layer in self.layers.iter() {
+      if layer.len() != self.width * self.height {
+        return Err(Error::msg(format!(
+          "tile layer has {} tiles instead of {}x{}",
+          layer.len(),
+          self.width,
+          self.height
+        )));
+      }
+
+      for (index, &id) in layer.iter().enumerate() {
+        let id = id & TILE_ID_MASK;
+        if id == 0 {
+          continue;
+        }
+
+        let tile = self
+          .tilesets
+          .iter()
+          .filter(|tileset| tileset.first_id <= id)
+          .max_by_key(|tileset| tileset.first_id)
+          .and_then(|tileset| tileset.tiles.get(&(id - tileset.first_id)));
+        if let Some(tile) = tile {
+          tiles[index / self.width][index % self.width] = tile.clone();
+        }
+      }
+    }
+
+    // Path tiles from the tile layers get their indices from the path objects
+    for row in tiles.iter_mut() {
+      for tile in row.iter_mut() {
+        if let Tile::Path(indices) = tile {
+          indices.clear();
+        }
+      }
+    }
+
+    let mut path_names = vec![];
+    for object in self.objects.iter() {
+      match object.class.to_lowercase().as_str() {
+        "spawn" | "end" => {
+          let center = Vec2::new(object.x + object.width / 2., object.y + object.height / 2.);
+          let Some((column, row)) = self.cell_at(center)
+            else {
+              warn!("Tiled object {} is outside of the map", object.name);
+              continue;
+            };
+          tiles[row][column] = if object.class.eq_ignore_ascii_case("spawn") {
+            Tile::Spawn
+          } else {
+            Tile::End
+          };
+        }
+        "path" => {
+          let index = path_names.len();
+          path_names.push(object.name.clone());
+
+          for (column, row) in self.path_cells(object) {
+            match &mut tiles[row][column] {
+              Tile::Path(indices) => {
+                if !indices.contains(&index) {
+                  indices.push(index);
+                }
+              }
+              // Spawns and ends are placed by their own objects
+              Tile::Spawn | Tile::End => {}
+              tile => *tile = Tile::Path(vec![index]),
+            }
+          }
+        }
+        _ => {}
+      }
+    }
+
+    // Path tiles that aren't under a path object belong to the first path
+    for row in tiles.iter_mut() {
+      for tile in row.iter_mut() {
+        if let Tile::Path(indices) = tile {
+          if indices.is_empty() {
+            indices.push(0);
+          }
+        }
+      }
+    }
+
+    Ok(Map {
+      width: self.width,
+      height: self.height,
+      tiles,
+      tile_size: self.tile_width,
+      path_names,
+      smooth_paths: self.smooth_paths,
+      paths: vec![],
+    })
+  }
+
+  // Tile (column, row) under a position in pixels, rows counted from the top
+  fn cell_at(&self, position: Vec2) -> Option<(usize, usize)> {
+    let column = (position.x / self.tile_width as f32).floor();
+    let row = (position.y / self.tile_height as f32).floor();
+    if column < 0. || row < 0. || column as usize >= self.width || row as usize >= self.height {
+      return None;
+    }
+
+    Some((column as usize, row as usize))
+  }
+
+  // Every tile the path's polyline goes through, or every tile of a path rectangle
+  fn path_cells(&self, object: &TiledObject) -> Vec<(usize, usize)> {
+    let origin = Vec2::new(object.x, object.y);
+    let mut cells = vec![];
+
+    if object.polyline.is_empty() {
+      let (Some(start), Some(end)) = (
+        self.cell_at(origin),
+        self.cell_at(origin + Vec2::new(object.width, object.height) - Vec2::ONE),
+      )
+        else { return cells; };
+      for row in start.1..=end.1 {
+        for column in start.0..=end.0 {
+          cells.push((column, row));
+        }
+      }
+      return cells;
+    }
+
+    let points = object
+      .polyline
+      .iter()
+      .filter_map(|point| self.cell_at(origin + *point))
+      .collect::<Vec<_>>();
+    let Some(&first) = points.first()
+      else { return cells; };
+    cells.push(first);
+
+    // Walk horizontally, then vertically between the points
+    let mut current = first;
+    for &(column, row) in points.iter().skip(1) {
+      while current.0 != column {
+        current.0 = if current.0 < column { current.0 + 1 } else { current.0 - 1 };
+        cells.push(current);
+      }
+      while current.1 != row {
+        current.1 = if current.1 < row { current.1 + 1 } else { current.1 - 1 };
+        cells.push(current);
+      }
+    }
+
+    cells
+  }
+}
+
+// Tile kind from a "tile" property or the tile's class
+fn tile_from_name(name: &str) -> Option<Tile> {
+  match name.to_lowercase().as_str() {
+    "grass" => Some(Tile::Grass),
+    "water" => Some(Tile::Water),
+    "spawn" => Some(Tile::Spawn),
+    "path" => Some(Tile::Path(vec![])),
+    "end" => Some(Tile::End),
+    "empty" => Some(Tile::Empty),
+    _ => None,
+  }
+}
+
+// Tile ids of a layer stored as CSV or as base64 of little endian u32s, optionally compressed
+fn decode_layer(data: &str, encoding: &str, compression: &str) -> Result<Vec<u32>, Error> {
+  match encoding {
+    "csv" => Ok(
+      data
+        .split(',')
+        .map(|id| id.trim().parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()?,
+    ),
+    "base64" => {
+      let bytes = base64::decode(data.trim())?;
+      let bytes = match compression {
+        "" => bytes,
+        "zlib" => decompress(ZlibDecoder::new(bytes.as_slice()))?,
+        "gzip" => decompress(GzDecoder::new(bytes.as_slice()))?,
+        _ => {
+          return Err(Error::msg(format!(
+            "{} compressed tile layers are not supported, use zlib, gzip or no compression",
+            compression
+          )))
+        }
+      };
+      if bytes.len() % 4 != 0 {
+        return Err(Error::msg("base64 tile layer data isn't a whole number of tile ids"));
+      }
+
+      Ok(
+        bytes
+          .chunks_exact(4)
+          .map(|id| u32::from_le_bytes([id[0], id[1], id[2], id[3]]))
+          .collect(),
+      )
+    }
+    _ => Err(Error::msg(format!(
+      "{} tile layer data is not supported, use CSV or base64",
+      encoding
+    ))),
+  }
+}
+
+fn decompress(mut decoder: impl Read) -> Result<Vec<u8>, Error> {
+  let mut bytes = vec![];
+  decoder.read_to_end(&mut bytes)?;
+  Ok(bytes)
+}
+
+// Tiled JSON format
+#[derive(Deserialize)]
+struct TmjMap {
+  width: usize,
+  height: usize,
+  tilewidth: usize,
+  tileheight: usize,
+  #[serde(default)]
+  infinite: bool,
+  #[serde(default)]
+  properties: Vec<TmjProperty>,
+  layers: Vec<TmjLayer>,
+  tilesets: Vec<TmjTileset>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum TmjLayer {
+  TileLayer {
+    // Array of tile ids for CSV, otherwise an encoded string
+    data: serde_json::Value,
+    #[serde(default)]
+    encoding: Option<String>,
+    #[serde(default)]
+    compression: Option<String>,
+  },
+  ObjectGroup {
+    objects: Vec<TmjObject>,
+  },
+  Group {
+    layers: Vec<TmjLayer>,
+  },
+  #[serde(other)]
+  Other,
+}
+
+#[derive(Deserialize)]
+struct TmjObject {
+  #[serde(default)]
+  name: String,
+  // Called type before Tiled 1.9
+  #[serde(default, alias = "type")]
+  class: String,
+  x: f32,
+  y: f32,
+  #[serde(default)]
+  width: f32,
+  #[serde(default)]
+  height: f32,
+  #[serde(default)]
+  polyline: Vec<TmjPoint>,
+}
+
+#[derive(Deserialize)]
+struct TmjPoint {
+  x: f32,
+  y: f32,
+}
+
+#[derive(Deserialize)]
+struct TmjTileset {
+  #[serde(default)]
+  firstgid: u32,
+  source: Option<String>,
+  #[serde(default)]
+  tiles: Vec<TmjTile>,
+}
+
+#[derive(Deserialize)]
+struct TmjTile {
+  id: u32,
+  #[serde(default, alias = "type")]
+  class: String,
+  #[serde(default)]
+  properties: Vec<TmjProperty>,
+}
+
+#[derive(Deserialize)]
+struct TmjProperty {
+  name: String,
+  value: serde_json::Value,
+}
+
+impl TmjMap {
+  fn into_tiled_map(self) -> Result<TiledMap, Error> {
+    if self.infinite {
+      return Err(Error::msg("infinite Tiled maps are not supported"));
+    }
+
+    let mut tiled_map = TiledMap {
+      width: self.width,
+      height: self.height,
+      tile_width: self.tilewidth,
+      tile_height: self.tileheight,
+      smooth_paths: self
+        .properties
+        .iter()
+        .any(|property| property.name == "smooth_paths" && property.value.as_bool() == Some(true)),
+      tilesets: self
+        .tilesets
+        .into_iter()
+        .map(|tileset| TiledTileset {
+          first_id: tileset.firstgid,
+          tiles: tileset.tiles(),
+          source: tileset.source,
+        })
+        .collect(),
+      ..default()
+    };
+    add_tmj_layers(&mut tiled_map, self.layers)?;
+
+    Ok(tiled_map)
+  }
+}
+
+fn add_tmj_layers(tiled_map: &mut TiledMap, layers: Vec<TmjLayer>) -> Result<(), Error> {
+  for layer in layers {
+    match layer {
+      TmjLayer::TileLayer {
+        data,
+        encoding,
+        compression,
+      } => {
+        let layer = match data {
+          serde_json::Value::String(data) => decode_layer(
+            &data,
+            encoding.as_deref().unwrap_or("base64"),
+            compression.as_deref().unwrap_or_default(),
+          )?,
+          data => serde_json::from_value(data)?,
+        };
+        tiled_map.layers.push(layer);
+      }
+      TmjLayer::ObjectGroup { objects } => {
+        tiled_map
+          .objects
+          .extend(objects.into_iter().map(|object| TiledObject {
+            name: object.name,
+            class: object.class,
+            x: object.x,
+            y: object.y,
+            width: object.width,
+            height: object.height,
+            polyline: object
+              .polyline
+              .iter()
+              .map(|point| Vec2::new(point.x, point.y))
+              .collect(),
+          }))
+      }
+      TmjLayer::Group { layers } => add_tmj_layers(tiled_map, layers)?,
+      TmjLayer::Other => {}
+    }
+  }
+
+  Ok(())
+}
+
+impl TmjTileset {
+  fn tiles(&self) -> HashMap<u32, Tile> {
+    self
+      .tiles
+      .iter()
+      .filter_map(|tile| {
+        let property = tile
+          .properties
+          .iter()
+          .find(|property| property.name == "tile")
+          .and_then(|property| property.value.as_str());
+        let tile_kind = tile_from_name(property.unwrap_or(&tile.class))?;
+
+        Some((tile.id, tile_kind))
+      })
+      .collect()
+  }
+}
+
+// Tiled XML format
+fn attribute<'a>(attributes: &'a [OwnedAttribute], name: &str) -> Option<&'a str> {
+  attributes
+    .iter()
+    .find(|attribute| attribute.name.local_name == name)
+    .map(|attribute| attribute.value.as_str())
+}
+
+fn parse_attribute<T: std::str::FromStr>(attributes: &[OwnedAttribute], name: &str) -> Option<T> {
+  attribute(attributes, name)?.parse().ok()
+}
+
+#[derive(Default)]
+struct TmxData {
+  encoding: Option<String>,
+  compression: Option<String>,
+  text: String,
+  // Tiles listed as elements
+  tiles: Vec<u32>,
+}
+
+fn parse_tmx(bytes: &[u8]) -> Result<TiledMap, Error> {
+  let mut tiled_map = TiledMap::default();
+  // Tile being read from the current tileset
+  let mut tile: Option<(u32, String)> = None;
+  // Layer data being read
+  let mut data: Option<TmxData> = None;
+  let mut in_object = false;
+
+  for event in EventReader::new(bytes) {
+    match event? {
+      XmlEvent::StartElement {
+        name, attributes, ..
+      } => match name.local_name.as_str() {
+        "map" => {
+          if attribute(&attributes, "infinite") == Some("1") {
+            return Err(Error::msg("infinite Tiled maps are not supported"));
+          }
+          tiled_map.width = parse_attribute(&attributes, "width").unwrap_or(0);
+          tiled_map.height = parse_attribute(&attributes, "height").unwrap_or(0);
+          tiled_map.tile_width = parse_attribute(&attributes, "tilewidth").unwrap_or(0);
+          tiled_map.tile_height = parse_attribute(&attributes, "tileheight").unwrap_or(0);
+        }
+        "tileset" => tiled_map.tilesets.push(TiledTileset {
+          first_id: parse_attribute(&attributes, "firstgid").unwrap_or(1),
+          source: attribute(&attributes, "source").map(String::from),
+          tiles: HashMap::default(),
+        }),
+        // Layer data without an encoding lists every tile as an element
+        "tile" if data.is_some() => {
+          if let Some(data) = data.as_mut() {
+            data.tiles.push(parse_attribute(&attributes, "gid").unwrap_or(0));
+          }
+        }
+        "tile" => {
+          let class = attribute(&attributes, "class")
+            .or_else(|| attribute(&attributes, "type"))
+            .unwrap_or_default();
+          tile = parse_attribute(&attributes, "id").map(|id| (id, class.to_string()));
+        }
+        "property" => match (tile.as_mut(), attribute(&attributes, "name")) {
+          (Some((_, class)), Some("tile")) => {
+            *class = attribute(&attributes, "value").unwrap_or_default().to_string();
+          }
+          (None, Some("smooth_paths")) if !in_object => {
+            tiled_map.smooth_paths = attribute(&attributes, "value") == Some("true");
+          }
+          _ => {}
+        },
+        "data" => {
+          data = Some(TmxData {
+            encoding: attribute(&attributes, "encoding").map(String::from),
+            compression: attribute(&attributes, "compression").map(String::from),
+            ..default()
+          });
+        }
+        "object" => {
+          in_object = true;
+          tiled_map.objects.push(TiledObject {
+            name: attribute(&attributes, "name").unwrap_or_default().to_string(),
+            class: attribute(&attributes, "class")
+              .or_else(|| attribute(&attributes, "type"))
+              .unwrap_or_default()
+              .to_string(),
+            x: parse_attribute(&attributes, "x").unwrap_or(0.),
+            y: parse_attribute(&attributes, "y").unwrap_or(0.),
+            width: parse_attribute(&attributes, "width").unwrap_or(0.),
+            height: parse_attribute(&attributes, "height").unwrap_or(0.),
+            polyline: vec![],
+          });
+        }
+        "polyline" if in_object => {
+          let points = attribute(&attributes, "points").unwrap_or_default();
+          if let Some(object) = tiled_map.objects.last_mut() {
+            object.polyline = parse_points(points);
+          }
+        }
+        _ => {}
+      },
+      XmlEvent::EndElement { name } => match name.local_name.as_str() {
+        "tile" if data.is_none() => {
+          if let Some((id, class)) = tile.take() {
+            if let (Some(tileset), Some(tile_kind)) =
+              (tiled_map.tilesets.last_mut(), tile_from_name(&class))
+            {
+              tileset.tiles.insert(id, tile_kind);
+            }
+          }
+        }
+        "data" => {
+          let Some(data) = data.take()
+            else { continue; };
+
+          let layer = match data.encoding {
+            Some(encoding) => decode_layer(
+              &data.text,
+              &encoding,
+              data.compression.as_deref().unwrap_or_default(),
+            )?,
+            None => data.tiles,
+          };
+          tiled_map.layers.push(layer);
+        }
+        "object" => in_object = false,
+        _ => {}
+      },
+      XmlEvent::Characters(text) => {
+        if let Some(data) = data.as_mut() {
+          data.text.push_str(&text);
+        }
+      }
+      _ => {}
+    }
+  }
+
+  Ok(tiled_map)
+}
+
+// Tiles of an external XML tileset
+fn parse_tsx(bytes: &[u8]) -> Result<HashMap<u32, Tile>, Error> {
+  // Same elements as a tileset inside a map
+  let mut tiled_map = parse_tmx(bytes)?;
+
+  Ok(
+    tiled_map
+      .tilesets
+      .pop()
+      .map(|tileset| tileset.tiles)
+      .unwrap_or_default(),
+  )
+}
+
+// "x,y x,y ..." from a polyline
+fn parse_points(points: &str) -> Vec<Vec2> {
+  points
+    .split_whitespace()
+    .filter_map(|point| {
+      let (x, y) = point.split_once(',')?;
+      Some(Vec2::new(x.parse().ok()?, y.parse().ok()?))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use flate2::write::{GzEncoder, ZlibEncoder};
+  use flate2::Compression;
+  use std::io::Write;
+
+  // 4x3 map, the second last tile is flipped horizontally
+  const IDS: [u32; 12] = [1, 1, 1, 1, 2, 2, 2, 2, 3, 0, 0x8000_0003, 1];
+
+  const TSX_TILES: &str = r#"
+    <tile id="0" class="Grass"/>
+    <tile id="1"><properties><property name="tile" value="Path"/></properties></tile>
+    <tile id="2" type="Water"/>"#;
+
+  fn expected_tiles() -> Vec<Vec<Tile>> {
+    use Tile::*;
+    vec![
+      vec![Grass, Grass, Grass, Grass],
+      vec![Spawn, Path(vec![0]), Path(vec![0]), End],
+      vec![Water, Grass, Water, Grass],
+    ]
+  }
+
+  fn csv() -> String {
+    IDS.map(|id| id.to_string()).join(",\n")
+  }
+
+  fn bytes() -> Vec<u8> {
+    IDS.iter().flat_map(|id| id.to_le_bytes()).collect()
+  }
+
+  fn zlib() -> String {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    encoder.write_all(&bytes()).unwrap();
+    base64::encode(encoder.finish().unwrap())
+  }
+
+  fn gzip() -> String {
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(&bytes()).unwrap();
+    base64::encode(encoder.finish().unwrap())
+  }
+
+  // `data` is the whole <data> element
+  fn tmx(tile_width: usize, data: &str) -> String {
+    format!(
+      r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="4" height="3" tilewidth="{tile_width}" tileheight="32" infinite="0">
+ <properties><property name="smooth_paths" type="bool" value="true"/></properties>
+ <tileset firstgid="1" name="tiles" tilewidth="32" tileheight="32">{TSX_TILES}
+ </tileset>
+ <layer id="1" name="Tiles" width="4" height="3">
+  {data}
+ </layer>
+ <objectgroup id="2" name="Objects">
+  <object id="1" name="start" class="Spawn" x="0" y="32" width="32" height="32"/>
+  <object id="2" name="finish" type="End" x="112" y="48"><point/></object>
+  <object id="3" name="Main" class="Path" x="16" y="48">
+   <properties><property name="smooth_paths" value="false"/></properties>
+   <polyline points="0,0 96,0"/>
+  </object>
+ </objectgroup>
+</map>"#
+    )
+  }
+
+  // `layer` holds the data fields of the tile layer
+  fn tmj(layer: &str) -> String {
+    format!(
+      r#"{{
+  "width": 4, "height": 3, "tilewidth": 32, "tileheight": 32, "infinite": false,
+  "properties": [{{ "name": "smooth_paths", "type": "bool", "value": true }}],
+  "tilesets": [{{
+    "firstgid": 1,
+    "tiles": [
+      {{ "id": 0, "class": "Grass" }},
+      {{ "id": 1, "properties": [{{ "name": "tile", "type": "string", "value": "Path" }}] }},
+      {{ "id": 2, "type": "Water" }}
+    ]
+  }}],
+  "layers": [
+    {{ "type": "group", "layers": [{{ "type": "tilelayer", "width": 4, "height": 3, {layer} }}] }},
+    {{ "type": "imagelayer" }},
+    {{ "type": "objectgroup", "objects": [
+      {{ "name": "start", "class": "Spawn", "x": 0, "y": 32, "width": 32, "height": 32 }},
+      {{ "name": "finish", "type": "End", "x": 112, "y": 48, "point": true }},
+      {{ "name": "Main", "class": "Path", "x": 16, "y": 48, "polyline": [{{ "x": 0, "y": 0 }}, {{ "x": 96, "y": 0 }}] }}
+    ]}}
+  ]
+}}"#
+    )
+  }
+
+  fn load(file_name: &str, text: &str) -> Result<Map, Error> {
+    parse_tiled_map(Path::new(file_name), text.as_bytes())?.into_map()
+  }
+
+  fn assert_expected_map(map: Map) {
+    assert_eq!(map.tiles, expected_tiles());
+    assert_eq!((map.width, map.height, map.tile_size), (4, 3, 32));
+    assert_eq!(map.path_names, vec!["Main".to_string()]);
+    assert!(map.smooth_paths);
+  }
+
+  #[test]
+  fn tmx_csv() {
+    let map = load("map.tmx", &tmx(32, &format!(r#"<data encoding="csv">{}</data>"#, csv())));
+    assert_expected_map(map.unwrap());
+  }
+
+  #[test]
+  fn tmx_base64() {
+    for (compression, data) in [
+      ("", base64::encode(bytes())),
+      (r#" compression="zlib""#, zlib()),
+      (r#" compression="gzip""#, gzip()),
+    ] {
+      let data = format!("<data encoding=\"base64\"{compression}>\n   {data}\n  </data>");
+      assert_expected_map(load("map.tmx", &tmx(32, &data)).unwrap());
+    }
+  }
+
+  #[test]
+  fn tmx_tile_elements() {
+    let tiles = IDS.map(|id| format!(r#"<tile gid="{id}"/>"#)).join("\n");
+    let map = load("map.tmx", &tmx(32, &format!("<data>{tiles}</data>")));
+    assert_expected_map(map.unwrap());
+  }
+
+  #[test]
+  fn tmj_encodings() {
+    let ids = IDS.map(|id| id.to_string()).join(", ");
+    for layer in [
+      format!(r#""data": [{ids}]"#),
+      format!(r#""encoding": "csv", "data": [{ids}]"#),
+      format!(r#""encoding": "base64", "data": "{}""#, base64::encode(bytes())),
+      format!(r#""encoding": "base64", "compression": "", "data": "{}""#, base64::encode(bytes())),
+      format!(r#""encoding": "base64", "compression": "zlib", "data": "{}""#, zlib()),
+      format!(r#""encoding": "base64", "compression": "gzip", "data": "{}""#, gzip()),
+    ] {
+      assert_expected_map(load("map.tmj", &tmj(&layer)).unwrap());
+    }
+  }
+
+  #[test]
+  fn unsupported_layer_data_is_an_error() {
+    let zstd = r#"<data encoding="base64" compression="zstd">KLUv/SAAAQAA</data>"#;
+    let error = load("map.tmx", &tmx(32, zstd)).err().unwrap();
+    assert!(error.to_string().contains("zstd"), "{error}");
+
+    let error = load("map.tmj", &tmj(r#""encoding": "base64", "compression": "zstd", "data": "KLUv/SAAAQAA""#));
+    assert!(error.err().unwrap().to_string().contains("zstd"));
+
+    let error = load("map.tmx", &tmx(32, r#"<data encoding="hex">00</data>"#)).err().unwrap();
+    assert!(error.to_string().contains("hex"), "{error}");
+
+    // Not a multiple of 4 bytes
+    let data = format!(r#"<data encoding="base64">{}</data>"#, base64::encode([1, 0, 0]));
+    assert!(load("map.tmx", &tmx(32, &data)).is_err());
+  }
+
+  #[test]
+  fn invalid_maps_are_errors() {
+    let data = format!(r#"<data encoding="csv">{}</data>"#, csv());
+    // Tiles aren't square
+    assert!(load("map.tmx", &tmx(16, &data)).is_err());
+    // Layer is missing a tile
+    assert!(load("map.tmx", &tmx(32, r#"<data encoding="csv">1,1,1</data>"#)).is_err());
+    // Infinite maps are stored in chunks
+    assert!(load("map.tmx", &tmx(32, &data).replace(r#"infinite="0""#, r#"infinite="1""#)).is_err());
+    assert!(load("map.tmj", &tmj(r#""data": [1]"#).replace(r#""infinite": false"#, r#""infinite": true"#)).is_err());
+  }
+
+  #[test]
+  fn external_tilesets() {
+    let expected = [(0, Tile::Grass), (1, Tile::Path(vec![])), (2, Tile::Water)]
+      .into_iter()
+      .collect::<HashMap<_, _>>();
+
+    let tsx = format!(r#"<?xml version="1.0" encoding="UTF-8"?><tileset name="tiles">{TSX_TILES}</tileset>"#);
+    assert_eq!(parse_tileset(Path::new("tiles.tsx"), tsx.as_bytes()).unwrap(), expected);
+
+    let tsj = r#"{ "name": "tiles", "tiles": [
+      { "id": 0, "class": "Grass" },
+      { "id": 1, "properties": [{ "name": "tile", "value": "Path" }] },
+      { "id": 2, "type": "Water" },
+      { "id": 3, "class": "Tree" }
+    ]}"#;
+    assert_eq!(parse_tileset(Path::new("tiles.tsj"), tsj.as_bytes()).unwrap(), expected);
+  }
+}
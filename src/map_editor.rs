@@ -0,0 +1,3 @@
+#[allow(clippy::module_inception)]
+mod map_editor;
+pub use map_editor::*;
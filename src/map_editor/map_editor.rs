@@ -0,0 +1,406 @@
+use bevy::prelude::*;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::assets::*;
+use crate::map::*;
+use crate::{window_to_world_pos, GameData, GameState, Levels, SelectedLevel};
+
+pub struct MapEditorPlugin;
+
+impl Plugin for MapEditorPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .add_systems(
+        (open_map_editor, spawn_map_editor_ui).in_schedule(OnEnter(GameState::MapEditor)),
+      )
+      .add_system(close_map_editor.in_schedule(OnExit(GameState::MapEditor)))
+      .add_systems(
+        (
+          editor_keyboard_input,
+          paint_tiles,
+          redraw_map.after(editor_keyboard_input).after(paint_tiles),
+        )
+          .in_set(OnUpdate(GameState::MapEditor)),
+      );
+  }
+}
+
+// Colours of the checkpoint preview, one per path index
+const PATH_COLORS: [Color; 4] = [Color::YELLOW, Color::CYAN, Color::FUCHSIA, Color::ORANGE];
+
+// Map being edited. Tiles are kept in file order, top row first
+#[derive(Resource)]
+pub struct MapEditor {
+  pub path: String,
+  pub map: Map,
+  pub brush: Tile,
+  pub path_index: usize,
+}
+
+// Marker for the tiles and checkpoint preview, redrawn on every change
+#[derive(Component)]
+pub struct MapEditorTile;
+
+#[derive(Component)]
+pub struct MapEditorUIRoot;
+
+#[derive(Component)]
+pub struct MapEditorText;
+
+// Read the map path from `--edit-map <file>`, relative to the assets folder
+fn map_path_from_args() -> Option<String> {
+  let args: Vec<String> = std::env::args().collect();
+  let index = args.iter().position(|arg| arg == "--edit-map")?;
+
+  args.get(index + 1).cloned()
+}
+
+// Map to edit and the path it's saved to. A missing file starts a new map.
+// Saving would overwrite a file that can't be read or parsed, so those aren't opened.
+// Tiled maps are saved to a `.map.ron` next to them, the editor can't write Tiled files
+fn read_editor_map(path: String) -> Result<(String, Map), String> {
+  if is_tiled_map(Path::new(&path)) {
+    let save_path = Path::new(&path)
+      .with_extension("map.ron")
+      .to_string_lossy()
+      .into_owned();
+    if Path::new(&format!("assets/{}", save_path)).exists() {
+      return Err(format!("{} was already imported to {}", path, save_path));
+    }
+
+    let map = read_tiled_map(Path::new(&format!("assets/{}", path)))
+      .map_err(|err| format!("Could not read Tiled map {}: {}", path, err))?;
+    info!("IMPORTED TILED MAP: {}, SAVING TO {}", path, save_path);
+    return Ok((save_path, map));
+  }
+
+  match fs::read_to_string(format!("assets/{}", path)) {
+    Ok(contents) => match ron::from_str(&contents) {
+      Ok(map) => Ok((path, map)),
+      Err(err) => Err(format!("Could not parse map {}: {}", path, err)),
+    },
+    Err(err) if err.kind() == io::ErrorKind::NotFound => {
+      info!("NEW MAP: {}", path);
+      let map = Map {
+        width: 16,
+        height: 9,
+        tiles: vec![vec![Tile::Grass; 16]; 9],
+        tile_size: 80,
+        path_names: vec![],
+        smooth_paths: false,
+        paths: vec![],
+      };
+      Ok((path, map))
+    }
+    Err(err) => Err(format!("Could not read map {}: {}", path, err)),
+  }
+}
+
+// Edit the map from `--edit-map`, or else the selected level's map
+fn open_map_editor(
+  mut commands: Commands,
+  game_data: Res<GameData>,
+  levels: Res<Assets<Levels>>,
+  selected_level: Res<SelectedLevel>,
+  mut game_state: ResMut<NextState<GameState>>,
+) {
+  let Some(path) = map_path_from_args().or_else(|| {
+    levels
+      .get(&game_data.levels)?
+      .levels
+      .get(selected_level.0)
+      .map(|level| level.map.clone())
+  })
+    else {
+      error!("No map to edit, pass one with --edit-map or select a level");
+      game_state.set(GameState::MainMenu);
+      return;
+    };
+
+  let (path, map) = match read_editor_map(path) {
+    Ok(opened) => opened,
+    Err(err) => {
+      error!("{}, not opening it", err);
+      game_state.set(GameState::MainMenu);
+      return;
+    }
+  };
+
+  info!("EDITING MAP: {}", path);
+
+  commands.insert_resource(MapEditor {
+    path,
+    map,
+    brush: Tile::Path(vec![0]),
+    path_index: 0,
+  });
+}
+
+#[allow(clippy::type_complexity)]
+fn close_map_editor(
+  mut commands: Commands,
+  entities: Query<Entity, Or<(With<MapEditorTile>, With<MapEditorUIRoot>)>>,
+) {
+  for entity in &entities {
+    commands.entity(entity).despawn_recursive();
+  }
+  commands.remove_resource::<MapEditor>();
+}
+
+// Brushes - 1 to 5, Path index - Q/E, Resize - Arrow keys, Save - Ctrl+S, Back to menu - Escape
+fn editor_keyboard_input(
+  keys: Res<Input<KeyCode>>,
+  editor: Option<ResMut<MapEditor>>,
+  mut game_state: ResMut<NextState<GameState>>,
+) {
+  // Leaving works even if no map could be opened
+  if keys.just_pressed(KeyCode::Escape) {
+    game_state.set(GameState::MainMenu);
+    return;
+  }
+
+  let Some(mut editor) = editor
+    else { return; };
+  let (width, height) = (editor.map.width, editor.map.height);
+
+  if keys.just_pressed(KeyCode::Key1) {
+    editor.brush = Tile::Grass;
+  } else if keys.just_pressed(KeyCode::Key2) {
+    editor.brush = Tile::Water;
+  } else if keys.just_pressed(KeyCode::Key3) {
+    editor.brush = Tile::Path(vec![editor.path_index]);
+  } else if keys.just_pressed(KeyCode::Key4) {
+    editor.brush = Tile::Spawn;
+  } else if keys.just_pressed(KeyCode::Key5) {
+    editor.brush = Tile::End;
+  } else if keys.just_pressed(KeyCode::Q) && editor.path_index > 0 {
+    editor.path_index -= 1;
+    editor.brush = Tile::Path(vec![editor.path_index]);
+  } else if keys.just_pressed(KeyCode::E) {
+    editor.path_index += 1;
+    editor.brush = Tile::Path(vec![editor.path_index]);
+  } else if keys.just_pressed(KeyCode::Right) {
+    editor.map.resize(width + 1, height);
+  } else if keys.just_pressed(KeyCode::Left) {
+    editor.map.resize(width - 1, height);
+  } else if keys.just_pressed(KeyCode::Up) {
+    editor.map.resize(width, height + 1);
+  } else if keys.just_pressed(KeyCode::Down) {
+    editor.map.resize(width, height - 1);
+  } else if (keys.pressed(KeyCode::LControl) || keys.pressed(KeyCode::RControl))
+    && keys.just_pressed(KeyCode::S)
+  {
+    save_map(&editor);
+  }
+}
+
+fn save_map(editor: &MapEditor) {
+  // Unfinished maps are saved anyway, the errors are only reported
+  report_map_errors(&editor.path, &editor.map);
+
+  // Every row of tiles on its own line, like the hand written maps
+  let config = ron::ser::PrettyConfig::default().depth_limit(2);
+  let contents = match ron::ser::to_string_pretty(&editor.map, config) {
+    Ok(contents) => contents,
+    Err(err) => {
+      error!("Could not serialize map: {}", err);
+      return;
+    }
+  };
+
+  let path = format!("assets/{}", editor.path);
+  match fs::write(&path, contents) {
+    Ok(()) => info!("MAP SAVED: {}", path),
+    Err(err) => error!("Could not write map {}: {}", path, err),
+  }
+}
+
+// Left click paints with the brush, right click erases to grass
+// or removes the current path index from a path tile
+fn paint_tiles(
+  mouse: Res<Input<MouseButton>>,
+  windows: Query<&Window>,
+  camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+  editor: Option<ResMut<MapEditor>>,
+) {
+  let Some(mut editor) = editor
+    else { return; };
+  let paint = mouse.pressed(MouseButton::Left);
+  let erase = mouse.pressed(MouseButton::Right);
+  if !paint && !erase {
+    return;
+  }
+
+  let window = windows.single();
+  let (camera, camera_transform) = camera_query.single();
+  let Some(cursor_position) = window.cursor_position()
+    else { return; };
+  let position = window_to_world_pos(window, cursor_position, camera, camera_transform);
+
+  let tile_size = editor.map.tile_size as f32;
+  let column = ((position.x + tile_size / 2.) / tile_size).floor();
+  let row = ((position.y + tile_size / 2.) / tile_size).floor();
+  if column < 0. || row < 0. {
+    return;
+  }
+  let (column, row) = (column as usize, row as usize);
+  if column >= editor.map.width || row >= editor.map.height {
+    return;
+  }
+  // The bottom row of the world is the last row in the file
+  let row = editor.map.height - 1 - row;
+
+  let current = &editor.map.tiles[row][column];
+  let new_tile = if paint {
+    match (current, &editor.brush) {
+      // Path tiles can be shared by several paths
+      (Tile::Path(indices), Tile::Path(_)) if !indices.contains(&editor.path_index) => {
+        let mut indices = indices.clone();
+        indices.push(editor.path_index);
+        indices.sort();
+        Tile::Path(indices)
+      }
+      (Tile::Path(_), Tile::Path(_)) => return,
+      (_, brush) => brush.clone(),
+    }
+  } else {
+    match current {
+      Tile::Path(indices) if indices.len() > 1 => Tile::Path(
+        indices
+          .iter()
+          .copied()
+          .filter(|&index| index != editor.path_index)
+          .collect(),
+      ),
+      _ => Tile::Grass,
+    }
+  };
+
+  // Only touch the resource on real changes, so the map isn't redrawn every frame
+  if editor.map.tiles[row][column] != new_tile {
+    editor.map.tiles[row][column] = new_tile;
+  }
+}
+
+fn redraw_map(
+  mut commands: Commands,
+  assets: Res<GameAssets>,
+  editor: Option<Res<MapEditor>>,
+  tiles: Query<Entity, With<MapEditorTile>>,
+  mut camera: Query<&mut Transform, With<MainCamera>>,
+  mut text: Query<&mut Text, With<MapEditorText>>,
+) {
+  let Some(editor) = editor
+    else { return; };
+  if !editor.is_changed() {
+    return;
+  }
+
+  for entity in &tiles {
+    commands.entity(entity).despawn_recursive();
+  }
+
+  let map = &editor.map;
+  let tile_size = map.tile_size as f32;
+
+  for (row, tiles) in map.tiles.iter().rev().enumerate() {
+    for (column, tile) in tiles.iter().enumerate() {
+      // Spawn and end share the path texture
+      let color = match tile {
+        Tile::Spawn => Color::GREEN,
+        Tile::End => Color::RED,
+        _ => Color::WHITE,
+      };
+
+      commands
+        .spawn(SpriteBundle {
+          texture: assets.get_tile(tile),
+          sprite: Sprite { color, ..default() },
+          transform: Transform::from_translation(Vec3::new(
+            column as f32 * tile_size,
+            row as f32 * tile_size,
+            0.,
+          )),
+          ..default()
+        })
+        .insert(MapEditorTile);
+    }
+  }
+
+  // Preview the checkpoints the game would build from these tiles
+  let mut preview = map.clone();
+  preview.tiles.reverse();
+  preview.create_paths();
+
+  for (index, path) in preview.paths.iter().enumerate() {
+    for checkpoint in &path.checkpoints {
+      commands
+        .spawn(SpriteBundle {
+          sprite: Sprite {
+            color: PATH_COLORS[index % PATH_COLORS.len()],
+            custom_size: Some(Vec2::splat(tile_size / 6.)),
+            ..default()
+          },
+          transform: Transform::from_translation(checkpoint.truncate().extend(1. + index as f32)),
+          ..default()
+        })
+        .insert(MapEditorTile);
+    }
+  }
+
+  for mut transform in camera.iter_mut() {
+    transform.translation.x = (map.width as f32 / 2. - 0.5) * tile_size;
+    transform.translation.y = (map.height as f32 / 2. - 0.5) * tile_size;
+  }
+
+  let path_names = preview
+    .paths
+    .iter()
+    .map(|path| format!("{} ({} checkpoints)", path.name, path.checkpoints.len()))
+    .collect::<Vec<_>>()
+    .join(", ");
+
+  for mut text in text.iter_mut() {
+    *text = Text::from_section(
+      format!(
+        "{}  {}x{}\nBrush: {:?}  Path index: {}\nPaths: {}\n\
+         1-5 brush, Q/E path index, arrows resize, Ctrl+S save, Esc menu",
+        editor.path, map.width, map.height, editor.brush, editor.path_index, path_names
+      ),
+      text.sections[0].style.clone(),
+    );
+  }
+}
+
+fn spawn_map_editor_ui(mut commands: Commands, assets: Res<GameAssets>) {
+  commands
+    .spawn(NodeBundle {
+      style: Style {
+        position_type: PositionType::Absolute,
+        position: UiRect {
+          left: Val::Percent(1.),
+          top: Val::Percent(1.),
+          ..default()
+        },
+        ..default()
+      },
+      background_color: BackgroundColor(Color::rgba(0., 0., 0., 0.6)),
+      ..default()
+    })
+    .insert(MapEditorUIRoot)
+    .insert(Name::new("MapEditorUI"))
+    .with_children(|commands| {
+      commands
+        .spawn(TextBundle::from_section(
+          "",
+          TextStyle {
+            font: assets.font.clone(),
+            font_size: 20.,
+            color: Color::WHITE,
+          },
+        ))
+        .insert(MapEditorText);
+    });
+}
@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::{Bullet, GameState};
+use crate::{Bullet, SimulationSet};
 
 pub struct MovementPlugin;
 
@@ -9,7 +9,11 @@ impl Plugin for MovementPlugin {
   fn build(&self, app: &mut App) {
     app
       .register_type::<Movement>()
-      .add_system(basic_movement.in_set(OnUpdate(GameState::Gameplay)));
+      .add_system(
+        basic_movement
+          .in_set(SimulationSet::Movement)
+          .in_schedule(CoreSchedule::FixedUpdate),
+      );
   }
 }
 
@@ -31,12 +35,13 @@ impl Movement {
   }
 }
 
-fn basic_movement(
+pub fn basic_movement(
   mut entities: Query<(&mut Movement, &mut Transform), With<Bullet>>,
-  time: Res<Time>,
+  fixed_time: Res<FixedTime>,
 ) {
   for (mut movement, mut transform) in &mut entities {
-    let distance = movement.direction.normalize() * movement.speed * time.delta_seconds();
+    let distance =
+      movement.direction.normalize() * movement.speed * fixed_time.period.as_secs_f32();
     movement.distance_travelled += distance.length();
     transform.translation += distance;
   }
@@ -0,0 +1,303 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::assets::*;
+use crate::tower::*;
+use crate::{
+  call_next_wave, GameRng, GameState, NextWaveCommand, PendingSnapshot, SelectedLevel,
+  SimulationSet, SimulationTick,
+};
+
+pub const REPLAY_PATH: &str = "replay.ron";
+
+const MIN_REPLAY_SPEED: f32 = 0.25;
+const MAX_REPLAY_SPEED: f32 = 16.;
+
+// Records player actions, or plays back the replay given with `--replay <file>`
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+  fn build(&self, app: &mut App) {
+    // Overrides the seed and level from the SimulationPlugin and LevelPlugin,
+    // so it has to be added after them
+    match replay_path_from_args().and_then(|path| read_replay(&path)) {
+      Some(replay) => {
+        info!("REPLAY SEED: {}", replay.seed);
+        app
+          .insert_resource(GameRng::from_seed(replay.seed))
+          .insert_resource(SelectedLevel(replay.level))
+          .insert_resource(ReplayPlayback {
+            replay,
+            next_action: 0,
+          });
+      }
+      None => {
+        let seed = app.world.resource::<GameRng>().seed;
+        app.insert_resource(ReplayRecorder(Replay {
+          seed,
+          level: 0,
+          actions: vec![],
+        }));
+      }
+    }
+
+    app
+      .add_systems(
+        (
+          play_back_actions.run_if(resource_exists::<ReplayPlayback>()),
+          record_actions.run_if(resource_exists::<ReplayRecorder>()),
+        )
+          .chain()
+          .before(apply_tower_commands)
+          .before(call_next_wave)
+          .in_set(SimulationSet::Commands)
+          .in_schedule(CoreSchedule::FixedUpdate),
+      )
+      .add_system(
+        skip_level_select
+          .run_if(resource_exists::<ReplayPlayback>())
+          .in_schedule(OnEnter(GameState::LevelSelect)),
+      )
+      .add_system(
+        record_level
+          .run_if(resource_exists::<ReplayRecorder>())
+          .in_schedule(OnEnter(GameState::Gameplay)),
+      )
+      .add_system(stop_recording_on_load.in_set(OnUpdate(GameState::Gameplay)));
+  }
+}
+
+// Speed control and indicator while a replay is playing
+pub struct ReplayUIPlugin;
+
+impl Plugin for ReplayUIPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .add_system(
+        spawn_replay_ui
+          .run_if(resource_exists::<ReplayPlayback>())
+          .in_schedule(OnEnter(GameState::Gameplay)),
+      )
+      .add_system(
+        replay_speed_control
+          .run_if(resource_exists::<ReplayPlayback>())
+          .in_set(OnUpdate(GameState::Gameplay)),
+      );
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ReplayAction {
+  PlaceTower(PlaceTowerCommand),
+  SellTower(SellTowerCommand),
+  UpgradeTower(UpgradeTowerCommand),
+  SetTargeting(SetTargetingCommand),
+  NextWave(NextWaveCommand),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TimedReplayAction {
+  pub tick: u64,
+  pub action: ReplayAction,
+}
+
+// Seed and player actions of a run. With the same waves it reproduces the whole run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Replay {
+  pub seed: u64,
+  // Index in the level registry
+  #[serde(default)]
+  pub level: usize,
+  pub actions: Vec<TimedReplayAction>,
+}
+
+#[derive(Resource)]
+pub struct ReplayRecorder(pub Replay);
+
+// Manual input is disabled while this resource exists
+#[derive(Resource)]
+pub struct ReplayPlayback {
+  pub replay: Replay,
+  pub next_action: usize,
+}
+
+#[derive(Component)]
+pub struct ReplaySpeedUI;
+
+// Read the replay path from `--replay <file>`
+fn replay_path_from_args() -> Option<String> {
+  let args: Vec<String> = std::env::args().collect();
+  let index = args.iter().position(|arg| arg == "--replay")?;
+
+  args.get(index + 1).cloned()
+}
+
+pub fn read_replay(path: &str) -> Option<Replay> {
+  let contents = fs::read_to_string(path)
+    .map_err(|err| error!("Could not read replay {}: {}", path, err))
+    .ok()?;
+
+  ron::from_str(&contents)
+    .map_err(|err| error!("Could not parse replay {}: {}", path, err))
+    .ok()
+}
+
+fn write_replay(path: &str, replay: &Replay) {
+  let contents = match ron::ser::to_string_pretty(replay, ron::ser::PrettyConfig::default()) {
+    Ok(contents) => contents,
+    Err(err) => {
+      error!("Could not serialize replay: {}", err);
+      return;
+    }
+  };
+
+  if let Err(err) = fs::write(path, contents) {
+    error!("Could not write replay {}: {}", path, err);
+  }
+}
+
+// The replay's level is already selected
+fn skip_level_select(mut game_state: ResMut<NextState<GameState>>) {
+  game_state.set(GameState::LevelLoading);
+}
+
+fn record_level(mut recorder: ResMut<ReplayRecorder>, selected_level: Res<SelectedLevel>) {
+  recorder.0.level = selected_level.0;
+}
+
+// Send the actions of the current tick, before they get applied
+fn play_back_actions(
+  tick: Res<SimulationTick>,
+  mut playback: ResMut<ReplayPlayback>,
+  mut place_commands: EventWriter<PlaceTowerCommand>,
+  mut sell_commands: EventWriter<SellTowerCommand>,
+  mut upgrade_commands: EventWriter<UpgradeTowerCommand>,
+  mut targeting_commands: EventWriter<SetTargetingCommand>,
+  mut next_wave_commands: EventWriter<NextWaveCommand>,
+) {
+  while let Some(timed_action) = playback.replay.actions.get(playback.next_action) {
+    if timed_action.tick > tick.0 {
+      break;
+    }
+
+    match timed_action.action.clone() {
+      ReplayAction::PlaceTower(command) => place_commands.send(command),
+      ReplayAction::SellTower(command) => sell_commands.send(command),
+      ReplayAction::UpgradeTower(command) => upgrade_commands.send(command),
+      ReplayAction::SetTargeting(command) => targeting_commands.send(command),
+      ReplayAction::NextWave(command) => next_wave_commands.send(command),
+    }
+
+    playback.next_action += 1;
+
+    if playback.next_action == playback.replay.actions.len() {
+      info!("REPLAY FINISHED: tick {}", tick.0);
+    }
+  }
+}
+
+// Save every player action with the tick it gets applied on
+fn record_actions(
+  tick: Res<SimulationTick>,
+  mut recorder: ResMut<ReplayRecorder>,
+  mut place_commands: EventReader<PlaceTowerCommand>,
+  mut sell_commands: EventReader<SellTowerCommand>,
+  mut upgrade_commands: EventReader<UpgradeTowerCommand>,
+  mut targeting_commands: EventReader<SetTargetingCommand>,
+  mut next_wave_commands: EventReader<NextWaveCommand>,
+) {
+  let actions = place_commands
+    .iter()
+    .cloned()
+    .map(ReplayAction::PlaceTower)
+    .chain(
+      upgrade_commands
+        .iter()
+        .cloned()
+        .map(ReplayAction::UpgradeTower),
+    )
+    .chain(
+      targeting_commands
+        .iter()
+        .cloned()
+        .map(ReplayAction::SetTargeting),
+    )
+    .chain(sell_commands.iter().cloned().map(ReplayAction::SellTower))
+    .chain(next_wave_commands.iter().cloned().map(ReplayAction::NextWave))
+    .map(|action| TimedReplayAction {
+      tick: tick.0,
+      action,
+    })
+    .collect::<Vec<_>>();
+
+  if actions.is_empty() {
+    return;
+  }
+
+  recorder.0.actions.extend(actions);
+
+  // Written right away, so the replay survives a crash or closing the window
+  write_replay(REPLAY_PATH, &recorder.0);
+}
+
+// A loaded save doesn't start from the recorded seed, so the replay can't reproduce it
+fn stop_recording_on_load(
+  mut commands: Commands,
+  snapshot: Option<Res<PendingSnapshot>>,
+  recorder: Option<Res<ReplayRecorder>>,
+) {
+  if snapshot.is_some() && recorder.is_some() {
+    commands.remove_resource::<ReplayRecorder>();
+    info!("REPLAY RECORDING STOPPED: game loaded from save");
+  }
+}
+
+// Speed up - Equals, Slow down - Minus
+fn replay_speed_control(
+  keys: Res<Input<KeyCode>>,
+  mut time: ResMut<Time>,
+  mut speed_ui: Query<&mut Text, With<ReplaySpeedUI>>,
+) {
+  let mut speed = time.relative_speed();
+
+  if keys.just_pressed(KeyCode::Equals) {
+    speed = (speed * 2.).min(MAX_REPLAY_SPEED);
+  } else if keys.just_pressed(KeyCode::Minus) {
+    speed = (speed / 2.).max(MIN_REPLAY_SPEED);
+  } else {
+    return;
+  }
+
+  time.set_relative_speed(speed);
+
+  for mut text in speed_ui.iter_mut() {
+    *text = Text::from_section(format!("Replay x{}", speed), text.sections[0].style.clone());
+  }
+}
+
+fn spawn_replay_ui(mut commands: Commands, assets: Res<GameAssets>) {
+  commands
+    .spawn(TextBundle {
+      style: Style {
+        position_type: PositionType::Absolute,
+        position: UiRect {
+          left: Val::Percent(45.),
+          top: Val::Percent(1.5),
+          ..default()
+        },
+        ..default()
+      },
+      text: Text::from_section(
+        "Replay x1",
+        TextStyle {
+          font: assets.font.clone(),
+          font_size: 36.,
+          color: Color::WHITE,
+        },
+      ),
+      ..default()
+    })
+    .insert(ReplaySpeedUI)
+    .insert(Name::new("ReplaySpeed"));
+}
@@ -0,0 +1,3 @@
+#[allow(clippy::module_inception)]
+mod save_game;
+pub use save_game::*;
@@ -0,0 +1,267 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::assets::*;
+use crate::enemy::*;
+use crate::gameplay_ui::*;
+use crate::movement::*;
+use crate::tower::*;
+use crate::{GameData, GameState, ReplayPlayback, SelectedLevel};
+
+pub const SAVE_PATH: &str = "savegame.ron";
+
+pub struct SaveGamePlugin;
+
+impl Plugin for SaveGamePlugin {
+  fn build(&self, app: &mut App) {
+    app.add_systems(
+      (
+        save_game.run_if(not(resource_exists::<ReplayPlayback>())),
+        load_game.run_if(not(resource_exists::<ReplayPlayback>())),
+        restore_snapshot,
+      )
+        .in_set(OnUpdate(GameState::Gameplay)),
+    );
+  }
+}
+
+// Everything needed to resume a game in progress
+#[derive(Serialize, Deserialize)]
+pub struct GameSnapshot {
+  // Index in the level registry, saves without one are from the first level
+  #[serde(default)]
+  pub level: usize,
+  pub money: usize,
+  pub base_health: i32,
+  pub current_wave: usize,
+  pub next_tower_id: u32,
+  pub wave_state: WaveState,
+  pub towers: Vec<TowerSnapshot>,
+  pub enemies: Vec<EnemySnapshot>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TowerSnapshot {
+  pub id: TowerId,
+  pub tower_type: TowerType,
+  pub position: Vec3,
+  pub rotation: Quat,
+  // Stats, upgrades, targeting priority and lifetime stats
+  pub tower: Tower,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EnemySnapshot {
+  pub enemy_type: EnemyType,
+  pub position: Vec3,
+  pub enemy: Enemy,
+  pub movement: Movement,
+  pub path: Path,
+  #[serde(default)]
+  pub status_effects: StatusEffects,
+  #[serde(default)]
+  pub traits: EnemyTraits,
+  // Wave the enemy counts towards
+  #[serde(default)]
+  pub wave: Option<usize>,
+}
+
+// Snapshot waiting to be applied to the running game
+#[derive(Resource)]
+pub struct PendingSnapshot(pub GameSnapshot);
+
+pub fn read_snapshot(path: &str) -> Option<GameSnapshot> {
+  let contents = fs::read_to_string(path)
+    .map_err(|err| error!("Could not read save file {}: {}", path, err))
+    .ok()?;
+
+  ron::from_str(&contents)
+    .map_err(|err| error!("Could not parse save file {}: {}", path, err))
+    .ok()
+}
+
+fn write_snapshot(path: &str, snapshot: &GameSnapshot) {
+  let contents = match ron::ser::to_string_pretty(snapshot, ron::ser::PrettyConfig::default()) {
+    Ok(contents) => contents,
+    Err(err) => {
+      error!("Could not serialize save file: {}", err);
+      return;
+    }
+  };
+
+  match fs::write(path, contents) {
+    Ok(()) => info!("GAME SAVED: {}", path),
+    Err(err) => error!("Could not write save file {}: {}", path, err),
+  }
+}
+
+// Quick save - F5
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn save_game(
+  keys: Res<Input<KeyCode>>,
+  selected_level: Res<SelectedLevel>,
+  player: Query<&Player>,
+  base: Query<&Base>,
+  game_data: Res<GameData>,
+  waves: Res<Assets<Waves>>,
+  wave_state: Res<WaveState>,
+  next_tower_id: Res<NextTowerId>,
+  towers: Query<(&TowerId, &Tower, &TowerType, &Transform)>,
+  enemies: Query<(
+    &EnemyType,
+    &Enemy,
+    &Movement,
+    &Path,
+    &StatusEffects,
+    &EnemyTraits,
+    &Transform,
+    Option<&FromWave>,
+  )>,
+) {
+  if !keys.just_pressed(KeyCode::F5) {
+    return;
+  }
+  let Some(waves) = waves.get(&game_data.enemy_waves)
+    else { return; };
+
+  let snapshot = GameSnapshot {
+    level: selected_level.0,
+    money: player.single().money,
+    base_health: base.single().health,
+    current_wave: waves.current,
+    next_tower_id: next_tower_id.0,
+    wave_state: wave_state.clone(),
+    towers: towers
+      .iter()
+      .map(|(id, tower, tower_type, transform)| TowerSnapshot {
+        id: *id,
+        tower_type: *tower_type,
+        position: transform.translation,
+        rotation: transform.rotation,
+        tower: tower.clone(),
+      })
+      .collect(),
+    enemies: enemies
+      .iter()
+      .map(
+        |(enemy_type, enemy, movement, path, status_effects, traits, transform, from_wave)| EnemySnapshot {
+          enemy_type: *enemy_type,
+          position: transform.translation,
+          enemy: enemy.clone(),
+          movement: movement.clone(),
+          path: path.clone(),
+          status_effects: status_effects.clone(),
+          traits: traits.clone(),
+          wave: from_wave.map(|from_wave| from_wave.0),
+        },
+      )
+      .collect(),
+  };
+
+  write_snapshot(SAVE_PATH, &snapshot);
+}
+
+// Quick load - F9
+fn load_game(
+  mut commands: Commands,
+  keys: Res<Input<KeyCode>>,
+  selected_level: Res<SelectedLevel>,
+) {
+  if !keys.just_pressed(KeyCode::F9) {
+    return;
+  }
+
+  let Some(snapshot) = read_snapshot(SAVE_PATH)
+    else { return; };
+
+  // The map and waves of another level aren't loaded, it can only be continued from the main menu
+  if snapshot.level != selected_level.0 {
+    warn!("The saved game is from another level");
+    return;
+  }
+
+  commands.insert_resource(PendingSnapshot(snapshot));
+}
+
+// Replace the running game with the pending snapshot
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn restore_snapshot(
+  mut commands: Commands,
+  snapshot: Option<Res<PendingSnapshot>>,
+  assets: Res<GameAssets>,
+  game_data: Res<GameData>,
+  mut waves: ResMut<Assets<Waves>>,
+  enemy_type_stats: Res<Assets<EnemyTypeStats>>,
+  tower_type_stats: Res<Assets<TowerTypeStats>>,
+  mut player: Query<&mut Player>,
+  mut base: Query<&mut Base>,
+  // Towers, enemies, tower UI and the tower being placed
+  despawn_query: Query<
+    Entity,
+    (
+      Or<(
+        With<Tower>,
+        With<Enemy>,
+        With<TowerUpgradeUI>,
+        With<SpriteFollower>,
+      )>,
+      Without<Parent>,
+    ),
+  >,
+) {
+  let Some(snapshot) = snapshot
+    else { return; };
+  let Some(waves) = waves.get_mut(&game_data.enemy_waves)
+    else { return; };
+  let Some(enemy_stats) = enemy_type_stats.get(&game_data.enemy_type_stats)
+    else { return; };
+  let Some(tower_stats) = tower_type_stats.get(&game_data.tower_type_stats)
+    else { return; };
+
+  let snapshot = &snapshot.0;
+
+  for entity in &despawn_query {
+    commands.entity(entity).despawn_recursive();
+  }
+
+  player.single_mut().money = snapshot.money;
+  base.single_mut().health = snapshot.base_health;
+  waves.current = snapshot.current_wave;
+  commands.insert_resource(snapshot.wave_state.clone());
+  commands.insert_resource(NextTowerId(snapshot.next_tower_id));
+
+  for saved_tower in &snapshot.towers {
+    let mut tower = saved_tower.tower_type.get_tower(tower_stats);
+    tower.tower = saved_tower.tower.clone();
+
+    let mut sprite = saved_tower
+      .tower_type
+      .get_sprite_sheet_bundle(&assets, saved_tower.position);
+    sprite.transform.rotation = saved_tower.rotation;
+
+    commands.spawn(tower).insert(sprite).insert(saved_tower.id);
+  }
+
+  for saved_enemy in &snapshot.enemies {
+    let mut enemy = enemy_stats.enemy[&saved_enemy.enemy_type].clone();
+    enemy.enemy = saved_enemy.enemy.clone();
+    enemy.movement = saved_enemy.movement.clone();
+    enemy.path = saved_enemy.path.clone();
+    enemy.status_effects = saved_enemy.status_effects.clone();
+    enemy.traits = saved_enemy.traits.clone();
+
+    let mut entity = commands.spawn(enemy);
+    entity.insert(
+      saved_enemy
+        .enemy_type
+        .get_sprite_sheet_bundle(&assets, saved_enemy.position),
+    );
+    if let Some(wave) = saved_enemy.wave {
+      entity.insert(FromWave(wave));
+    }
+  }
+
+  commands.remove_resource::<PendingSnapshot>();
+  info!("GAME LOADED");
+}
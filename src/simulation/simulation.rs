@@ -0,0 +1,129 @@
+use bevy::ecs::schedule::{LogLevel, ScheduleBuildSettings};
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::ops::{Deref, DerefMut};
+use std::time::Duration;
+
+use crate::GameState;
+
+// Length of one gameplay tick. Gameplay always steps by this amount, independent of frame rate
+pub const SIMULATION_TIMESTEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+  fn build(&self, app: &mut App) {
+    let seed = seed_from_args().unwrap_or_else(|| rand::thread_rng().gen());
+    info!("GAME_SEED: {}", seed);
+
+    app
+      .insert_resource(FixedTime::new(SIMULATION_TIMESTEP))
+      .insert_resource(GameRng::from_seed(seed))
+      .init_resource::<SimulationTick>()
+      .add_system(
+        advance_tick
+          .after(SimulationSet::Rewards)
+          .run_if(in_state(GameState::Gameplay))
+          .in_schedule(CoreSchedule::FixedUpdate),
+      )
+      .edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
+        // Warns about gameplay systems that conflict without an order
+        schedule.set_build_settings(ScheduleBuildSettings {
+          ambiguity_detection: LogLevel::Warn,
+          ..default()
+        });
+        schedule.configure_sets(
+          (
+            SimulationSet::Commands,
+            SimulationSet::Spawn,
+            SimulationSet::Movement,
+            SimulationSet::Indexing,
+            SimulationSet::Shooting,
+            SimulationSet::Collision,
+            SimulationSet::Cleanup,
+            SimulationSet::Rewards,
+          )
+            .chain(),
+        );
+        for set in [
+          SimulationSet::Commands,
+          SimulationSet::Spawn,
+          SimulationSet::Movement,
+          SimulationSet::Indexing,
+          SimulationSet::Shooting,
+          SimulationSet::Collision,
+          SimulationSet::Cleanup,
+          SimulationSet::Rewards,
+        ] {
+          schedule.configure_set(set.run_if(in_state(GameState::Gameplay)));
+        }
+      });
+  }
+}
+
+// Gameplay systems run in the fixed update schedule, in this order, on every tick.
+// Systems in a set that touch the same data are ordered as well, so every run is the same
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum SimulationSet {
+  // Replay, then tower commands and the next wave command
+  Commands,
+  Spawn,
+  // Bullets, then enemies
+  Movement,
+  // Spatial index of the moved enemies
+  Indexing,
+  Shooting,
+  // Bullet hits, bullets out of range, then damage over time
+  Collision,
+  // Enemy traits, bosses, dead enemies, then leaked enemies
+  Cleanup,
+  // Kills, cleared waves, then early called waves
+  Rewards,
+}
+
+// Number of gameplay ticks since the game started
+#[derive(Resource, Default)]
+pub struct SimulationTick(pub u64);
+
+fn advance_tick(mut tick: ResMut<SimulationTick>) {
+  tick.0 += 1;
+}
+
+// Every random choice in gameplay goes through this, so a seed reproduces a whole run
+#[derive(Resource)]
+pub struct GameRng {
+  pub seed: u64,
+  rng: StdRng,
+}
+
+impl GameRng {
+  pub fn from_seed(seed: u64) -> Self {
+    Self {
+      seed,
+      rng: StdRng::seed_from_u64(seed),
+    }
+  }
+}
+
+impl Deref for GameRng {
+  type Target = StdRng;
+
+  fn deref(&self) -> &Self::Target {
+    &self.rng
+  }
+}
+
+impl DerefMut for GameRng {
+  fn deref_mut(&mut self) -> &mut Self::Target {
+    &mut self.rng
+  }
+}
+
+// Read the seed from `--seed <number>`
+fn seed_from_args() -> Option<u64> {
+  let args: Vec<String> = std::env::args().collect();
+  let index = args.iter().position(|arg| arg == "--seed")?;
+
+  args.get(index + 1)?.parse().ok()
+}
@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 mod tower;
 pub use tower::*;
 
@@ -19,5 +20,11 @@ pub use tower_upgrade::*;
 mod tower_ui;
 pub use tower_ui::*;
 
+mod tower_command;
+pub use tower_command::*;
+
 mod targeting_priority;
 pub use targeting_priority::*;
+
+mod damage_type;
+pub use damage_type::*;
@@ -3,7 +3,7 @@ use bevy::sprite::collide_aabb::collide;
 
 use crate::enemy::*;
 use crate::movement::*;
-use crate::{GameState, Tower};
+use crate::{DamageType, GameData, SimulationSet, Tower, TowerId, TowerType, TowerTypeStats};
 
 pub struct BulletPlugin;
 
@@ -11,7 +11,12 @@ impl Plugin for BulletPlugin {
   fn build(&self, app: &mut App) {
     app
       .register_type::<Bullet>()
-      .add_systems((despawn_bullets, bullet_enemy_collision).in_set(OnUpdate(GameState::Gameplay)));
+      .add_systems(
+        (bullet_enemy_collision, despawn_bullets)
+          .chain()
+          .in_set(SimulationSet::Collision)
+          .in_schedule(CoreSchedule::FixedUpdate),
+      );
   }
 }
 
@@ -27,16 +32,17 @@ pub struct BulletBundle {
 #[reflect(Component)]
 pub struct Bullet {
   pub damage: u32,
+  pub damage_type: DamageType,
   pub lifetime: Timer, // !!! fix?
 }
 
 fn despawn_bullets(
   mut commands: Commands,
   mut bullets: Query<(Entity, &mut Bullet)>,
-  time: Res<Time>,
+  fixed_time: Res<FixedTime>,
 ) {
   for (entity, mut bullet) in &mut bullets {
-    bullet.lifetime.tick(time.delta());
+    bullet.lifetime.tick(fixed_time.period);
     // If the lifetime timer finished, despawn bullet
     if bullet.lifetime.finished() {
       // Despawn entities and their children
@@ -45,33 +51,97 @@ fn despawn_bullets(
   }
 }
 
-fn bullet_enemy_collision(
+const BULLET_SIZE: Vec2 = Vec2::new(40., 22.);
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn bullet_enemy_collision(
   mut commands: Commands,
-  bullets: Query<(Entity, &Bullet, &Parent, &GlobalTransform)>,
-  mut enemies: Query<(&mut Enemy, &Transform)>,
-  mut towers: Query<&mut Tower>,
+  bullets: Query<(Entity, &Bullet, &Parent, &Transform)>,
+  mut enemies: Query<
+    (
+      &mut Enemy,
+      &EnemyType,
+      &mut StatusEffects,
+      &mut EnemyTraits,
+      &mut LastHitBy,
+      &Transform,
+      Option<&Immune>,
+    ),
+    Without<Bullet>,
+  >,
+  mut towers: Query<
+    (&mut Tower, &TowerType, &TowerId, &Transform),
+    (Without<Bullet>, Without<Enemy>),
+  >,
+  game_data: Res<GameData>,
+  enemy_stats: Res<Assets<EnemyTypeStats>>,
+  tower_stats: Res<Assets<TowerTypeStats>>,
+  grid: Res<EnemyGrid>,
 ) {
+  let Some(enemy_stats) = enemy_stats.get(&game_data.enemy_type_stats)
+    else { return; };
+  let Some(tower_stats) = tower_stats.get(&game_data.tower_type_stats)
+    else { return; };
+
   for (bullet_entity, bullet, tower_parent, bullet_transform) in &bullets {
-    for (mut enemy, enemy_transform) in &mut enemies {
+    // Bullets are children of their tower, so their world position is relative to it
+    let Ok((_, _, _, tower_transform)) = towers.get(tower_parent.get())
+      else { continue; };
+    let bullet_position = tower_transform.transform_point(bullet_transform.translation);
+
+    // Only the enemies near the bullet
+    for enemy_entity in grid.overlapping(bullet_position, BULLET_SIZE / 2.) {
+      let Ok((
+        mut enemy,
+        enemy_type,
+        mut status_effects,
+        mut traits,
+        mut last_hit_by,
+        enemy_transform,
+        immune,
+      )) = enemies.get_mut(enemy_entity)
+        else { continue; };
+
+      // Bosses are scaled up
       if collide(
-        bullet_transform.translation(),
-        Vec2::new(40., 22.),
+        bullet_position,
+        BULLET_SIZE,
         enemy_transform.translation,
-        Vec2::new(30., 30.),
+        ENEMY_SIZE * enemy_transform.scale.truncate(),
       )
       .is_some()
       {
+        // Immune enemies absorb the bullet without taking damage
+        if immune.is_some() {
+          commands.entity(bullet_entity).despawn_recursive();
+          break;
+        }
+
+        // Resistances and weaknesses of the enemy to the bullet's element, and curses
+        let damage = enemy.scale_damage(
+          bullet.damage,
+          enemy_stats.damage_multiplier(*enemy_type, bullet.damage_type)
+            * status_effects.damage_taken_multiplier(),
+        );
+        // Shields take the damage first
+        let damage = traits.absorb(damage);
+
         // Update tower's total damage
-        let mut tower = towers.get_mut(tower_parent.get()).unwrap();
-        if enemy.health >= bullet.damage as i32 {
-          tower.total_damage += bullet.damage;
+        let (mut tower, tower_type, tower_id, _) = towers.get_mut(tower_parent.get()).unwrap();
+        if enemy.health >= damage as i32 {
+          tower.total_damage += damage;
         } else {
-          tower.total_damage += enemy.health as u32;
+          tower.total_damage += enemy.health.max(0) as u32;
         }
 
         // Despawn bullet upon hit and damage enemy
         commands.entity(bullet_entity).despawn_recursive();
-        enemy.health -= bullet.damage as i32;
+        enemy.health -= damage as i32;
+        last_hit_by.0 = Some(tower_parent.get());
+
+        for effect in tower_stats.effects.get(tower_type).into_iter().flatten() {
+          status_effects.apply(effect, *tower_id, bullet.damage_type);
+        }
         break;
       }
     }
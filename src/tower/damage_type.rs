@@ -0,0 +1,30 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+use crate::tower::*;
+
+// Element of a tower's attacks. Enemies can resist or be weak to each of them
+#[derive(
+  Reflect, Display, Default, Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+pub enum DamageType {
+  Nature,
+  Fire,
+  Ice,
+  Dark,
+  #[default]
+  Arcane,
+}
+
+impl TowerType {
+  pub fn damage_type(&self) -> DamageType {
+    match self {
+      TowerType::Nature => DamageType::Nature,
+      TowerType::Fire => DamageType::Fire,
+      TowerType::Ice => DamageType::Ice,
+      TowerType::Dark => DamageType::Dark,
+      TowerType::Mage | TowerType::Archmage => DamageType::Arcane,
+    }
+  }
+}
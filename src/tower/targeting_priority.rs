@@ -6,6 +6,7 @@ use strum_macros::EnumIter;
 
 use crate::enemy::*;
 use crate::movement::*;
+use crate::GameRng;
 
 #[derive(
   EnumIter, Reflect, Clone, Debug, Component, Default, PartialEq, Serialize, Deserialize,
@@ -48,30 +49,36 @@ impl TargetingPriority {
 }
 
 pub fn get_enemy_direction(
-  enemies: &Query<(&GlobalTransform, &Enemy, &Movement)>,
+  enemies: &Query<(&Transform, &Enemy, &Movement, &EnemyTraits)>,
+  grid: &EnemyGrid,
   bullet_spawn_pos: Vec3,
   tower_range: u32,
   tower_targeting_priority: &TargetingPriority,
+  detection: bool,
+  rng: &mut GameRng,
 ) -> Option<Vec3> {
-  let enemy_filtered_query = enemies
-    .iter()
+  let enemy_filtered_query = grid
+    .in_radius(bullet_spawn_pos, tower_range as f32)
+    .filter_map(|entity| enemies.get(entity).ok())
     // Filter the enemies that are in the tower's range
     .filter(|(enemy_transform, ..)| {
-      Vec3::distance(enemy_transform.translation(), bullet_spawn_pos) <= tower_range as f32
-    });
+      Vec3::distance(enemy_transform.translation, bullet_spawn_pos) <= tower_range as f32
+    })
+    // Camo enemies can only be seen by towers with detection
+    .filter(|(.., traits)| !traits.camo || detection);
 
   let enemy = match tower_targeting_priority {
     TargetingPriority::FIRST => enemy_filtered_query
       // Find first enemy that is closest to the base
-      .max_by_key(|(.., movement)| FloatOrd(movement.distance_travelled)),
+      .max_by_key(|(_, _, movement, _)| FloatOrd(movement.distance_travelled)),
     TargetingPriority::LAST => enemy_filtered_query
       // Find first enemy that is closest to the base
-      .min_by_key(|(.., movement)| FloatOrd(movement.distance_travelled)),
+      .min_by_key(|(_, _, movement, _)| FloatOrd(movement.distance_travelled)),
     TargetingPriority::CLOSE => enemy_filtered_query
       // Find enemy that is closest to the tower
       .min_by_key(|(enemy_transform, ..)| {
         FloatOrd(Vec3::distance(
-          enemy_transform.translation(),
+          enemy_transform.translation,
           bullet_spawn_pos,
         ))
       }),
@@ -79,7 +86,7 @@ pub fn get_enemy_direction(
       // Find enemy that is the farthest away from the tower
       .max_by_key(|(enemy_transform, ..)| {
         FloatOrd(Vec3::distance(
-          enemy_transform.translation(),
+          enemy_transform.translation,
           bullet_spawn_pos,
         ))
       }),
@@ -91,12 +98,12 @@ pub fn get_enemy_direction(
       .min_by_key(|(_, enemy, ..)| FloatOrd(enemy.health as f32)),
     TargetingPriority::RANDOM => enemy_filtered_query
       // Choose a random enemy
-      .choose(&mut rand::thread_rng()),
+      .choose(&mut **rng),
   };
 
   if let Some((enemy, ..)) = enemy {
     // return direction
-    return Option::from(enemy.translation() - bullet_spawn_pos);
+    return Option::from(enemy.translation - bullet_spawn_pos);
   }
   None
 }
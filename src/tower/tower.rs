@@ -1,5 +1,4 @@
 use bevy::prelude::*;
-use bevy::sprite::Mesh2dHandle;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
@@ -7,7 +6,7 @@ use crate::assets::*;
 use crate::enemy::*;
 use crate::movement::*;
 use crate::tower::*;
-use crate::GameState;
+use crate::{GameRng, SimulationSet};
 
 pub struct TowerPlugin;
 
@@ -16,7 +15,11 @@ impl Plugin for TowerPlugin {
     app
       .register_type::<Tower>()
       .register_type::<TargetingPriority>()
-      .add_system(tower_shooting.in_set(OnUpdate(GameState::Gameplay)));
+      .add_system(
+        tower_shooting
+          .in_set(SimulationSet::Shooting)
+          .in_schedule(CoreSchedule::FixedUpdate),
+      );
   }
 }
 
@@ -42,6 +45,9 @@ pub struct Tower {
   pub shooting_timer: Timer,
   pub total_spent: u32,
   pub total_damage: u32,
+  // Can target camo enemies
+  #[serde(default)]
+  pub detection: bool,
   // Flag to stop timer from counting when there are no enemies
   pub first_enemy_appeared: bool,
 }
@@ -71,13 +77,7 @@ impl Tower {
     }
   }
 
-  pub fn upgrade(
-    &mut self,
-    upgrade: &Upgrade,
-    path_index: usize,
-    meshes: &mut Assets<Mesh>,
-    tower_range_radius: &mut Query<&mut Mesh2dHandle>,
-  ) {
+  pub fn upgrade(&mut self, upgrade: &Upgrade, path_index: usize) {
     // Update total spent and sell price of tower
     self.total_spent += upgrade.cost as u32;
     self.sell_price = self.total_spent / 3;
@@ -92,12 +92,8 @@ impl Tower {
             .shooting_timer
             .set_duration(Duration::from_millis((1000. * self.attack_speed) as u64));
         }
-        TowerStat::Range => {
-          self.range += *v as u32;
-          for mut radius in tower_range_radius.iter_mut() {
-            radius.0 = meshes.add(shape::Circle::new(self.range as f32).into());
-          }
-        }
+        TowerStat::Range => self.range += *v as u32,
+        TowerStat::Detection => self.detection |= *v != 0,
       }
     }
 
@@ -110,54 +106,39 @@ pub fn spawn_tower(
   tower_type: TowerType,
   assets: &GameAssets,
   position: Vec3,
-  meshes: &mut Assets<Mesh>,
-  materials: &mut Assets<ColorMaterial>,
   tower_stats: &TowerTypeStats,
-) {
+  tower_id: TowerId,
+) -> Entity {
   commands
     .spawn(tower_type.get_tower(tower_stats))
     .insert(tower_type.get_sprite_sheet_bundle(assets, position))
-    .with_children(|commands| {
-      commands
-        .spawn(spawn_tower_range(
-          meshes,
-          materials,
-          tower_stats.tower[&tower_type].tower.range,
-        ))
-        .insert(Name::new("Tower Range"))
-        .insert(TowerUpgradeUI);
-    });
-
-  // Spawn Tower UI - Targeting priority, Selling & Upgrades
-  spawn_tower_ui(
-    commands,
-    assets,
-    &tower_stats.tower[&tower_type].tower,
-    tower_type,
-    position,
-  );
+    .insert(tower_id)
+    .id()
 }
 
 fn tower_shooting(
   mut commands: Commands,
   assets: Res<GameAssets>, // Bullet assets
-  mut towers: Query<(
-    Entity,
-    &mut Tower,
-    &TowerType,
-    &mut Transform,
-    &GlobalTransform,
-  )>,
-  enemies: Query<(&GlobalTransform, &Enemy, &Movement)>,
-  time: Res<Time>,
+  mut towers: Query<(Entity, &mut Tower, &TowerType, &mut Transform), Without<Enemy>>,
+  enemies: Query<(&Transform, &Enemy, &Movement, &EnemyTraits)>,
+  grid: Res<EnemyGrid>,
+  mut rng: ResMut<GameRng>,
+  fixed_time: Res<FixedTime>,
 ) {
-  for (tower_entity, mut tower, tower_type, mut tower_transform, transform) in &mut towers {
+  for (tower_entity, mut tower, tower_type, mut tower_transform) in &mut towers {
     // Check if an enemy is in range so we can tick the timer
-    if enemy_in_range(&tower, &tower_transform, &enemies) {
-      let bullet_spawn_pos = transform.translation() + tower.bullet_spawn_offset;
-
-      let direction =
-        get_enemy_direction(&enemies, bullet_spawn_pos, tower.range + 10, &tower.target);
+    if enemy_in_range(&tower, &tower_transform, &enemies, &grid) {
+      let bullet_spawn_pos = tower_transform.translation + tower.bullet_spawn_offset;
+
+      let direction = get_enemy_direction(
+        &enemies,
+        &grid,
+        bullet_spawn_pos,
+        tower.range + 10,
+        &tower.target,
+        tower.detection,
+        &mut rng,
+      );
 
       // If there is an enemy in the tower's range (if direction != None), then shoot bullet
       if let Some(direction) = direction {
@@ -185,7 +166,7 @@ fn tower_shooting(
           });
         }
 
-        tower.shooting_timer.tick(time.delta());
+        tower.shooting_timer.tick(fixed_time.period);
       }
     } else {
       tower.shooting_timer.reset();
@@ -197,15 +178,16 @@ fn tower_shooting(
 fn enemy_in_range(
   tower: &Mut<Tower>,
   tower_transform: &Mut<Transform>,
-  enemies: &Query<(&GlobalTransform, &Enemy, &Movement)>,
+  enemies: &Query<(&Transform, &Enemy, &Movement, &EnemyTraits)>,
+  grid: &EnemyGrid,
 ) -> bool {
-  for (enemy_transform, ..) in enemies {
-    if Vec3::distance(tower_transform.translation, enemy_transform.translation())
-      <= (tower.range + 50) as f32
-    {
-      return true;
-    }
-  }
-
-  false
+  let range = (tower.range + 50) as f32;
+
+  grid
+    .in_radius(tower_transform.translation, range)
+    .filter_map(|entity| enemies.get(entity).ok())
+    .any(|(enemy_transform, .., traits)| {
+      (!traits.camo || tower.detection)
+        && Vec3::distance(tower_transform.translation, enemy_transform.translation) <= range
+    })
 }
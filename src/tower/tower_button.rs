@@ -4,7 +4,7 @@ use strum::IntoEnumIterator;
 
 use crate::assets::*;
 use crate::tower::*;
-use crate::{GameData, GameState, GameplayUIRoot, MainCamera, Player};
+use crate::{GameData, GameState, GameplayUIRoot, MainCamera, Map, Player, ReplayPlayback};
 
 pub struct TowerButtonPlugin;
 
@@ -14,8 +14,8 @@ impl Plugin for TowerButtonPlugin {
       .add_system(generate_ui.in_schedule(OnEnter(GameState::Gameplay)))
       .add_systems(
         (
-          tower_button_interaction,
-          place_tower,
+          tower_button_interaction.run_if(not(resource_exists::<ReplayPlayback>())),
+          place_tower.run_if(not(resource_exists::<ReplayPlayback>())),
           lock_tower_buttons.after(generate_ui),
         )
           .in_set(OnUpdate(GameState::Gameplay)),
@@ -107,6 +107,7 @@ pub fn cursor_above_ui<T: Component>(
   false
 }
 
+#[allow(clippy::too_many_arguments)]
 fn place_tower(
   mut commands: Commands,
   mut query: Query<
@@ -119,27 +120,29 @@ fn place_tower(
     With<SpriteFollower>,
   >,
   assets: Res<GameAssets>,
-  mouse: Res<Input<MouseButton>>,
-  keys: Res<Input<KeyCode>>,
+  // Grouped, systems take at most 16 parameters
+  (mouse, keys): (Res<Input<MouseButton>>, Res<Input<KeyCode>>),
   windows: Query<&Window>,
   camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
-  mut player: Query<&mut Player>,
+  player: Query<&Player>,
   towers: Query<&Transform, (With<Tower>, Without<SpriteFollower>)>,
   mut clicked_tower: Query<Entity, With<TowerUpgradeUI>>,
   mut meshes: ResMut<Assets<Mesh>>,
   mut materials: ResMut<Assets<ColorMaterial>>,
   game_data: Res<GameData>,
-  tower_stats: Res<Assets<TowerTypeStats>>,
+  (tower_stats, maps): (Res<Assets<TowerTypeStats>>, Res<Assets<Map>>),
   node_query: Query<(&Node, &GlobalTransform, &Visibility), With<GameplayUIRoot>>,
-  //tilemap: Res<Map>,
+  mut place_commands: EventWriter<PlaceTowerCommand>,
   mut cursor_exited_ui: ResMut<CursorExitedUI>, // Flag to check initial mouse exit from button UI
 ) {
   let Some(tower_stats) = tower_stats.get(&game_data.tower_type_stats)
     else { return; };
+  let Some(map) = maps.get(&game_data.map)
+    else { return; };
 
   let window = windows.get_single().unwrap();
   let (camera, camera_transform) = camera_query.single();
-  let mut player = player.single_mut();
+  let player = player.single();
 
   for (entity, mut transform, tower_type, mut color) in query.iter_mut() {
     if !clicked_tower.is_empty() {
@@ -155,30 +158,6 @@ fn place_tower(
 
       transform.translation = window_to_world_pos(window, position, camera, camera_transform);
 
-      // Tower range becomes red when trying to place on path/invalid tile
-      // for (y, row) in tilemap.tiles.iter().enumerate() {
-      //   let mouse_on_path = row.iter().enumerate().filter(|(x, tile)| {
-      //     if matches!(tile, Tile::Path(_)) &&
-      //       position.x >= (*x as f32 * tilemap.tile_size as f32 -
-      //        tilemap.tile_size as f32 / 2.) as f32 &&
-      //       position.y >= (y as f32 * tilemap.tile_size as f32 -
-      //        tilemap.tile_size as f32 / 2.) as f32 &&
-      //       position.x <= (*x as f32 * tilemap.tile_size as f32 +
-      //        tilemap.tile_size as f32 / 2.) as f32 &&
-      //       position.y <= (y as f32 * tilemap.tile_size as f32 +
-      //        tilemap.tile_size as f32 / 2.) as f32 {
-      //       println!("{:?}, {:?}, {:?}", position, x, y);
-      //       return true;
-      //     }
-      //     false
-      //   }).last();
-      //   if mouse_on_path.is_some() {
-      //     println!("ON PATH");
-      //     *color = materials.add(ColorMaterial::from(
-      //       Color::rgba_u8(202, 0, 0, 150)));
-      //   }
-      // }
-
       let mouse_on_placed_tower = towers
         .iter()
         .filter(|tower_transform| {
@@ -186,7 +165,10 @@ fn place_tower(
         })
         .last();
 
-      if mouse_on_placed_tower.is_some() {
+      // Tower range becomes red when trying to place on a tile the tower can't stand on
+      let tiles_allowed = tower_tiles_allowed(transform.translation, *tower_type, map, tower_stats);
+
+      if mouse_on_placed_tower.is_some() || !tiles_allowed {
         *color = materials.add(ColorMaterial::from(Color::rgba_u8(202, 0, 0, 150)));
       } else {
         *color = materials.add(ColorMaterial::from(Color::rgba_u8(0, 0, 0, 85)));
@@ -199,25 +181,20 @@ fn place_tower(
         cursor_exited_ui.0 = false;
         let mouse_click_pos = window_to_world_pos(window, screen_pos, camera, camera_transform);
 
-        let mut place_tower = true;
-
-        for tower_transform in towers.iter() {
-          if Vec3::distance(mouse_click_pos, tower_transform.translation) <= 40. {
-            place_tower = false;
-          }
-        }
-        if place_tower {
-          player.money -= tower_stats.tower[tower_type].tower.price as usize;
+        if tower_position_free(
+          mouse_click_pos,
+          towers
+            .iter()
+            .map(|tower_transform| tower_transform.translation),
+        ) && tower_tiles_allowed(mouse_click_pos, *tower_type, map, tower_stats)
+        {
           commands.entity(entity).despawn_recursive();
-          spawn_tower(
-            &mut commands,
-            *tower_type,
-            &assets,
-            mouse_click_pos,
-            &mut meshes,
-            &mut materials,
-            tower_stats,
-          );
+          // The tower is spawned on the next gameplay tick, if the placement is still valid
+          place_commands.send(PlaceTowerCommand {
+            tower_type: *tower_type,
+            position: mouse_click_pos,
+            from_input: true,
+          });
         }
       }
     }
@@ -240,7 +217,7 @@ fn place_tower(
       tower_spawn_from_keyboard_input(
         &mut commands,
         &keys,
-        &player,
+        player,
         window,
         camera,
         camera_transform,
@@ -253,6 +230,7 @@ fn place_tower(
   }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn spawn_sprite_follower(
   commands: &mut Commands,
   window: &Window,
@@ -291,6 +269,7 @@ fn spawn_sprite_follower(
   }
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn tower_button_interaction(
   mut commands: Commands,
   assets: Res<GameAssets>,
@@ -381,6 +360,7 @@ fn tower_button_interaction(
   }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn tower_spawn_from_keyboard_input(
   commands: &mut Commands,
   keys: &Input<KeyCode>,
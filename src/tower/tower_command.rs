@@ -0,0 +1,233 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::assets::*;
+use crate::tower::*;
+use crate::{GameData, Map, Player, SimulationSet};
+
+pub struct TowerCommandPlugin;
+
+impl Plugin for TowerCommandPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .register_type::<TowerId>()
+      .init_resource::<NextTowerId>()
+      // Commands are sent every frame but only read on gameplay ticks, so they are
+      // cleared by the tick instead of every frame, otherwise they could get dropped
+      .init_resource::<Events<PlaceTowerCommand>>()
+      .init_resource::<Events<SellTowerCommand>>()
+      .init_resource::<Events<UpgradeTowerCommand>>()
+      .init_resource::<Events<SetTargetingCommand>>()
+      .add_event::<TowerPlacedEvent>()
+      .add_event::<TowerUpgradedEvent>()
+      .add_event::<TowerSoldEvent>()
+      .add_systems(
+        (
+          apply_tower_commands,
+          Events::<PlaceTowerCommand>::update_system,
+          Events::<SellTowerCommand>::update_system,
+          Events::<UpgradeTowerCommand>::update_system,
+          Events::<SetTargetingCommand>::update_system,
+        )
+          .chain()
+          .in_set(SimulationSet::Commands)
+          .in_schedule(CoreSchedule::FixedUpdate),
+      );
+  }
+}
+
+// Stable tower identifier, so commands can refer to towers across runs
+#[derive(
+  Component, Reflect, Clone, Copy, Default, Debug, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+#[reflect(Component)]
+pub struct TowerId(pub u32);
+
+#[derive(Resource, Default)]
+pub struct NextTowerId(pub u32);
+
+// Player actions. Input, replays and bots only send these,
+// apply_tower_commands() validates them and changes the game
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlaceTowerCommand {
+  pub tower_type: TowerType,
+  pub position: Vec3,
+  // Placed with the mouse, not by a replay or bot. Not saved, so replays play it back as false
+  #[serde(skip)]
+  pub from_input: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SellTowerCommand {
+  pub tower: TowerId,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UpgradeTowerCommand {
+  pub tower: TowerId,
+  pub path_index: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SetTargetingCommand {
+  pub tower: TowerId,
+  pub target: TargetingPriority,
+}
+
+// Results of applied commands, so the UI can react to them
+
+pub struct TowerPlacedEvent {
+  pub tower: Entity,
+  pub from_input: bool,
+}
+
+pub struct TowerUpgradedEvent {
+  pub tower: Entity,
+}
+
+pub struct TowerSoldEvent {
+  pub tower: Entity,
+}
+
+// Minimum distance between two towers
+pub const TOWER_SPACING: f32 = 40.;
+
+// Half the size of the square a tower stands on
+pub const TOWER_FOOTPRINT: f32 = 20.;
+
+pub fn tower_position_free(position: Vec3, mut towers: impl Iterator<Item = Vec3>) -> bool {
+  towers.all(|tower_position| Vec3::distance(position, tower_position) > TOWER_SPACING)
+}
+
+// Every tile under the tower's footprint has to be one the tower type can stand on
+pub fn tower_tiles_allowed(
+  position: Vec3,
+  tower_type: TowerType,
+  map: &Map,
+  tower_stats: &TowerTypeStats,
+) -> bool {
+  [
+    Vec3::ZERO,
+    Vec3::new(-TOWER_FOOTPRINT, -TOWER_FOOTPRINT, 0.),
+    Vec3::new(-TOWER_FOOTPRINT, TOWER_FOOTPRINT, 0.),
+    Vec3::new(TOWER_FOOTPRINT, -TOWER_FOOTPRINT, 0.),
+    Vec3::new(TOWER_FOOTPRINT, TOWER_FOOTPRINT, 0.),
+  ]
+  .iter()
+  .all(|offset| match map.tile_at(position + *offset) {
+    Some(tile) => tower_stats.can_stand_on(tower_type, tile),
+    None => false,
+  })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn apply_tower_commands(
+  mut commands: Commands,
+  assets: Res<GameAssets>,
+  game_data: Res<GameData>,
+  tower_stats: Res<Assets<TowerTypeStats>>,
+  upgrades: Res<Assets<Upgrades>>,
+  map: Res<Assets<Map>>,
+  mut next_tower_id: ResMut<NextTowerId>,
+  mut player: Query<&mut Player>,
+  mut towers: Query<(Entity, &TowerId, &mut Tower, &TowerType, &Transform)>,
+  mut place_commands: EventReader<PlaceTowerCommand>,
+  mut upgrade_commands: EventReader<UpgradeTowerCommand>,
+  mut targeting_commands: EventReader<SetTargetingCommand>,
+  mut sell_commands: EventReader<SellTowerCommand>,
+  mut placed_writer: EventWriter<TowerPlacedEvent>,
+  mut upgraded_writer: EventWriter<TowerUpgradedEvent>,
+  mut sold_writer: EventWriter<TowerSoldEvent>,
+) {
+  let Some(tower_stats) = tower_stats.get(&game_data.tower_type_stats)
+    else { return; };
+  let Some(upgrades) = upgrades.get(&game_data.tower_upgrades)
+    else { return; };
+  let Some(map) = map.get(&game_data.map)
+    else { return; };
+
+  let mut player = player.single_mut();
+
+  // Towers placed this tick aren't in the query yet
+  let mut placed_positions: Vec<Vec3> = vec![];
+
+  for command in place_commands.iter() {
+    let price = tower_stats.tower[&command.tower_type].tower.price as usize;
+    let position_free = tower_position_free(
+      command.position,
+      towers
+        .iter()
+        .map(|(.., transform)| transform.translation)
+        .chain(placed_positions.iter().copied()),
+    );
+
+    let tiles_allowed = tower_tiles_allowed(command.position, command.tower_type, map, tower_stats);
+
+    if player.money >= price && position_free && tiles_allowed {
+      player.money -= price;
+
+      let tower_id = TowerId(next_tower_id.0);
+      next_tower_id.0 += 1;
+
+      let tower = spawn_tower(
+        &mut commands,
+        command.tower_type,
+        &assets,
+        command.position,
+        tower_stats,
+        tower_id,
+      );
+      placed_positions.push(command.position);
+      placed_writer.send(TowerPlacedEvent {
+        tower,
+        from_input: command.from_input,
+      });
+    }
+  }
+
+  for command in upgrade_commands.iter() {
+    let Some((entity, _, mut tower, tower_type, _)) = towers
+      .iter_mut()
+      .find(|(_, tower_id, ..)| **tower_id == command.tower)
+      else { continue; };
+    let Some(tower_upgrades) = upgrades.upgrades[tower_type].get(command.path_index)
+      else { continue; };
+
+    let i = tower.upgrades.upgrades[command.path_index];
+
+    if i < tower_upgrades.len() && player.money >= tower_upgrades[i].cost {
+      player.money -= tower_upgrades[i].cost;
+      tower.upgrade(&tower_upgrades[i], command.path_index);
+      upgraded_writer.send(TowerUpgradedEvent { tower: entity });
+    }
+  }
+
+  for command in targeting_commands.iter() {
+    if let Some((.., mut tower, _, _)) = towers
+      .iter_mut()
+      .find(|(_, tower_id, ..)| **tower_id == command.tower)
+    {
+      tower.target = command.target.clone();
+    }
+  }
+
+  // Towers sold this tick are only despawned at the end of it
+  let mut sold_towers: Vec<TowerId> = vec![];
+
+  for command in sell_commands.iter() {
+    if sold_towers.contains(&command.tower) {
+      continue;
+    }
+
+    if let Some((entity, _, tower, ..)) = towers
+      .iter()
+      .find(|(_, tower_id, ..)| **tower_id == command.tower)
+    {
+      sold_towers.push(command.tower);
+      commands.entity(entity).despawn_recursive();
+      player.money += (tower.total_spent / 3) as usize;
+      sold_writer.send(TowerSoldEvent { tower: entity });
+    }
+  }
+}
@@ -3,19 +3,29 @@ use bevy::sprite::Mesh2dHandle;
 
 use crate::assets::*;
 use crate::tower::*;
-use crate::{GameData, GameState, MainCamera, Player};
+use crate::{GameState, MainCamera, ReplayPlayback};
 
 pub struct TowerSelectionPlugin;
 
 impl Plugin for TowerSelectionPlugin {
   fn build(&self, app: &mut App) {
-    app.add_systems((mouse_click, tower_ui_interaction).in_set(OnUpdate(GameState::Gameplay)));
+    app.add_systems(
+      (
+        mouse_click.run_if(not(resource_exists::<ReplayPlayback>())),
+        tower_ui_interaction.run_if(not(resource_exists::<ReplayPlayback>())),
+        show_placed_tower_ui,
+        update_upgraded_tower_range,
+        hide_sold_tower_ui,
+      )
+        .in_set(OnUpdate(GameState::Gameplay)),
+    );
   }
 }
 
 #[derive(Component)]
 pub struct TowerUpgradeUI;
 
+#[allow(clippy::too_many_arguments)]
 fn mouse_click(
   mut commands: Commands,
   assets: Res<GameAssets>,
@@ -51,6 +61,7 @@ fn mouse_click(
   }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn mouse_click_interaction(
   commands: &mut Commands,
   assets: &GameAssets,
@@ -89,17 +100,14 @@ fn mouse_click_interaction(
   }
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn tower_ui_interaction(
-  //assets: Res<GameAssets>,
-  mut commands: Commands,
-  mut towers: Query<(Entity, &mut Tower, &TowerType, &Children)>,
+  towers: Query<(&TowerId, &Tower, &Children)>,
   clicked_tower: Query<Entity, With<TowerUpgradeUI>>,
   keys: Res<Input<KeyCode>>,
-  mut player: Query<&mut Player>,
-  game_data: Res<GameData>,
-  upgrades: Res<Assets<Upgrades>>,
-  mut meshes: ResMut<Assets<Mesh>>,
-  mut tower_range_radius: Query<&mut Mesh2dHandle>,
+  mut sell_commands: EventWriter<SellTowerCommand>,
+  mut upgrade_commands: EventWriter<UpgradeTowerCommand>,
+  mut targeting_commands: EventWriter<SetTargetingCommand>,
   // UI Buttons
   //mut images: Query<(&mut UiImage, With<SellButton>)>,
   prev_target_button_interaction: Query<
@@ -127,160 +135,164 @@ fn tower_ui_interaction(
     (Changed<Interaction>, With<Button>),
   >,
 ) {
-  let Some(upgrades) = upgrades.get(&game_data.tower_upgrades)
-    else { return; };
+  if clicked_tower.is_empty() {
+    return;
+  }
 
-  if !clicked_tower.is_empty() {
-    let mut player = player.single_mut();
+  // Only send commands here, apply_tower_commands() checks money and upgrade levels
+  for (tower_id, tower, children) in towers.iter() {
+    for _ in clicked_tower.iter_many(children) {
+      let mut sell = false;
+      let mut upgrade_path_index: Option<usize> = None;
+      let mut target = tower.target.clone();
 
-    // Keyboard shortcuts
-    for (entity, mut tower, tower_type, children) in towers.iter_mut() {
-      for _ in clicked_tower.iter_many(children) {
-        let mut upgrade_path_index: Option<usize> = None;
+      // Keyboard shortcuts
 
-        // Sell tower
-        if keys.just_pressed(KeyCode::Back) {
-          // Despawn tower
-          commands.entity(entity).despawn_recursive();
-          // Despawn UI
-          for entity in clicked_tower.iter() {
-            commands.entity(entity).despawn_recursive();
-          }
-          player.money += (tower.total_spent / 3) as usize;
-        }
-        // Upgrade tower - Path 1
-        else if keys.just_pressed(KeyCode::Comma) {
-          upgrade_path_index = Some(0);
-        }
-        // Upgrade tower - Path 2
-        else if keys.just_pressed(KeyCode::Period) {
-          upgrade_path_index = Some(1);
-        }
-        // Upgrade tower - Path 3
-        else if keys.just_pressed(KeyCode::Slash) {
-          upgrade_path_index = Some(2);
-        }
-        // Change targeting priority (left)
-        else if (keys.pressed(KeyCode::LControl) || keys.pressed(KeyCode::RControl))
-          && keys.just_pressed(KeyCode::Tab)
-        {
-          tower.target.prev_target();
+      // Sell tower
+      if keys.just_pressed(KeyCode::Back) {
+        sell = true;
+      }
+      // Upgrade tower - Path 1
+      else if keys.just_pressed(KeyCode::Comma) {
+        upgrade_path_index = Some(0);
+      }
+      // Upgrade tower - Path 2
+      else if keys.just_pressed(KeyCode::Period) {
+        upgrade_path_index = Some(1);
+      }
+      // Upgrade tower - Path 3
+      else if keys.just_pressed(KeyCode::Slash) {
+        upgrade_path_index = Some(2);
+      }
+      // Change targeting priority (left)
+      else if (keys.pressed(KeyCode::LControl) || keys.pressed(KeyCode::RControl))
+        && keys.just_pressed(KeyCode::Tab)
+      {
+        target.prev_target();
+      }
+      // Change targeting priority (right)
+      else if keys.just_pressed(KeyCode::Tab) {
+        target.next_target();
+      }
+
+      // Button interaction
+
+      // Targeting priority - Previous target
+      for interaction in &prev_target_button_interaction {
+        if *interaction == Interaction::Clicked {
+          target.prev_target();
         }
-        // Change targeting priority (right)
-        else if keys.just_pressed(KeyCode::Tab) {
-          tower.target.next_target();
+      }
+
+      // Targeting priority - Next target
+      for interaction in &next_target_button_interaction {
+        if *interaction == Interaction::Clicked {
+          target.next_target();
         }
+      }
 
-        // Upgrade
-        if let Some(path_index) = upgrade_path_index {
-          let i = tower.upgrades.upgrades[path_index];
-          let tower_upgrades = &upgrades.upgrades[tower_type][path_index];
+      // Sell button
+      for interaction in &sell_button_interaction {
+        if *interaction == Interaction::Clicked {
+          sell = true;
+        }
+      }
 
-          if i < tower_upgrades.len() && player.money >= tower_upgrades[i].cost {
-            player.money -= tower_upgrades[i].cost;
-            tower.upgrade(
-              &tower_upgrades[i],
-              path_index,
-              &mut meshes,
-              &mut tower_range_radius,
-            );
-          }
+      // Upgrade buttons
+      for (interaction, state) in &upgrade_button_interaction {
+        if *interaction == Interaction::Clicked {
+          upgrade_path_index = Some(state.path_index);
         }
+      }
 
-        // Button interaction
+      if target != tower.target {
+        targeting_commands.send(SetTargetingCommand {
+          tower: *tower_id,
+          target,
+        });
+      }
 
-        // Targeting priority - Previous target
-        for interaction in &prev_target_button_interaction {
-          match interaction {
-            Interaction::Clicked => {
-              // Change button UI
-              // for (mut image) in images.iter_mut() {
-              // }
+      if let Some(path_index) = upgrade_path_index {
+        upgrade_commands.send(UpgradeTowerCommand {
+          tower: *tower_id,
+          path_index,
+        });
+      }
 
-              tower.target.prev_target();
-            }
-            Interaction::Hovered => {
-              // Change button UI !!!
-            }
-            Interaction::None => {
-              // Change button UI !!!
-            }
-          }
-        }
+      if sell {
+        sell_commands.send(SellTowerCommand { tower: *tower_id });
+      }
+    }
+  }
+}
 
-        // Targeting priority - Next target
-        for interaction in &next_target_button_interaction {
-          match interaction {
-            Interaction::Clicked => {
-              // Change button UI
-              // for (mut image) in images.iter_mut() {
-              // }
-              tower.target.next_target();
-            }
-            Interaction::Hovered => {
-              // Change button UI !!!
-            }
-            Interaction::None => {
-              // Change button UI !!!
-            }
-          }
-        }
+// Show the range and upgrade UI of a tower the player just placed.
+// Towers placed by replays or bots don't take the selection
+fn show_placed_tower_ui(
+  mut commands: Commands,
+  assets: Res<GameAssets>,
+  mut placed_events: EventReader<TowerPlacedEvent>,
+  towers: Query<(&Tower, &TowerType, &Transform)>,
+  tower_ui: Query<Entity, (With<TowerUpgradeUI>, Without<Parent>)>,
+  mut meshes: ResMut<Assets<Mesh>>,
+  mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+  // Only the last placed tower gets selected
+  let Some(event) = placed_events.iter().filter(|event| event.from_input).last()
+    else { return; };
+  let Ok((tower, tower_type, transform)) = towers.get(event.tower)
+    else { return; };
 
-        // Sell button
-        for interaction in &sell_button_interaction {
-          match interaction {
-            Interaction::Clicked => {
-              // Change button UI
-              // for (mut image) in images.iter_mut() {
-              // }
+  for entity in tower_ui.iter() {
+    commands.entity(entity).despawn_recursive();
+  }
 
-              // Despawn tower
-              commands.entity(entity).despawn_recursive();
-              // Despawn UI
-              for entity in clicked_tower.iter() {
-                commands.entity(entity).despawn_recursive();
-              }
-              player.money += (tower.total_spent / 3) as usize;
-            }
-            Interaction::Hovered => {
-              // Change button UI !!!
-            }
-            Interaction::None => {
-              // Change button UI !!!
-            }
-          }
-        }
+  commands.entity(event.tower).with_children(|commands| {
+    commands
+      .spawn(spawn_tower_range(&mut meshes, &mut materials, tower.range))
+      .insert(Name::new("Tower Range"))
+      .insert(TowerUpgradeUI);
+  });
 
-        // Upgrade buttons
-        for (interaction, state) in &upgrade_button_interaction {
-          let i = tower.upgrades.upgrades[state.path_index];
-          let tower_upgrades = &upgrades.upgrades[tower_type][state.path_index];
+  spawn_tower_ui(
+    &mut commands,
+    &assets,
+    tower,
+    *tower_type,
+    transform.translation,
+  );
+}
 
-          if i < tower_upgrades.len() && player.money >= tower_upgrades[i].cost {
-            match interaction {
-              Interaction::Clicked => {
-                // Change button UI
-                // for (mut image) in images.iter_mut() {
-                // }
+// Resize the range circle of an upgraded tower
+fn update_upgraded_tower_range(
+  mut upgraded_events: EventReader<TowerUpgradedEvent>,
+  towers: Query<(&Tower, &Children)>,
+  mut tower_range_radius: Query<&mut Mesh2dHandle, With<TowerUpgradeUI>>,
+  mut meshes: ResMut<Assets<Mesh>>,
+) {
+  for event in upgraded_events.iter() {
+    let Ok((tower, children)) = towers.get(event.tower)
+      else { continue; };
 
-                player.money -= tower_upgrades[i].cost;
-                tower.upgrade(
-                  &tower_upgrades[i],
-                  state.path_index,
-                  &mut meshes,
-                  &mut tower_range_radius,
-                );
-              }
-              Interaction::Hovered => {
-                // Change button UI !!!
-              }
-              Interaction::None => {
-                // Change button UI !!!
-              }
-            }
-          }
-        }
+    for &child in children.iter() {
+      if let Ok(mut radius) = tower_range_radius.get_mut(child) {
+        radius.0 = meshes.add(shape::Circle::new(tower.range as f32).into());
       }
     }
   }
 }
+
+// Close the upgrade UI of a sold tower
+fn hide_sold_tower_ui(
+  mut commands: Commands,
+  mut sold_events: EventReader<TowerSoldEvent>,
+  tower_ui: Query<Entity, (With<TowerUpgradeUI>, Without<Parent>)>,
+) {
+  if sold_events.iter().count() == 0 {
+    return;
+  }
+
+  for entity in tower_ui.iter() {
+    commands.entity(entity).despawn_recursive();
+  }
+}
@@ -5,8 +5,10 @@ use serde::{Deserialize, Serialize};
 use strum_macros::{Display, EnumIter};
 
 use crate::assets::*;
+use crate::map::*;
 use crate::movement::*;
 use crate::tower::*;
+use crate::StatusEffect;
 
 #[derive(
   EnumIter, Component, Display, Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize,
@@ -24,6 +26,24 @@ pub enum TowerType {
 #[uuid = "410719fd-234e-4e88-8549-4ff3004041a9"]
 pub struct TowerTypeStats {
   pub tower: HashMap<TowerType, TowerBundle>,
+  // Tiles each tower type can stand on, towers that aren't listed can only stand on grass.
+  // Only the kind of tile matters, `Path([])` allows any path tile
+  #[serde(default)]
+  pub placement: HashMap<TowerType, Vec<Tile>>,
+  // Status effects each tower type applies to the enemies it hits
+  #[serde(default)]
+  pub effects: HashMap<TowerType, Vec<StatusEffect>>,
+}
+
+impl TowerTypeStats {
+  pub fn can_stand_on(&self, tower_type: TowerType, tile: &Tile) -> bool {
+    match self.placement.get(&tower_type) {
+      Some(tiles) => tiles
+        .iter()
+        .any(|allowed| std::mem::discriminant(allowed) == std::mem::discriminant(tile)),
+      None => *tile == Tile::Grass,
+    }
+  }
 }
 
 impl TowerType {
@@ -53,6 +73,7 @@ impl TowerType {
       TowerType::Nature => BulletBundle {
         bullet: Bullet {
           damage,
+          damage_type: self.damage_type(),
           lifetime: Timer::from_seconds(1.25, TimerMode::Once),
         },
         movement: Movement::new(Vec3::new(0.00000001, 0., 0.), 1500.),
@@ -66,6 +87,7 @@ impl TowerType {
       TowerType::Fire => BulletBundle {
         bullet: Bullet {
           damage,
+          damage_type: self.damage_type(),
           lifetime: Timer::from_seconds(1.25, TimerMode::Once),
         },
         movement: Movement::new(Vec3::new(0.00000001, 0., 0.), 1500.),
@@ -79,6 +101,7 @@ impl TowerType {
       TowerType::Ice => BulletBundle {
         bullet: Bullet {
           damage,
+          damage_type: self.damage_type(),
           lifetime: Timer::from_seconds(1.25, TimerMode::Once),
         },
         movement: Movement::new(Vec3::new(0.00000001, 0., 0.), 1500.),
@@ -92,6 +115,7 @@ impl TowerType {
       TowerType::Dark => BulletBundle {
         bullet: Bullet {
           damage,
+          damage_type: self.damage_type(),
           lifetime: Timer::from_seconds(1.25, TimerMode::Once),
         },
         movement: Movement::new(Vec3::new(0.00000001, 0., 0.), 1500.),
@@ -105,6 +129,7 @@ impl TowerType {
       TowerType::Mage => BulletBundle {
         bullet: Bullet {
           damage,
+          damage_type: self.damage_type(),
           lifetime: Timer::from_seconds(1.25, TimerMode::Once),
         },
         movement: Movement::new(Vec3::new(0.00000001, 0., 0.), 1500.),
@@ -118,6 +143,7 @@ impl TowerType {
       TowerType::Archmage => BulletBundle {
         bullet: Bullet {
           damage,
+          damage_type: self.damage_type(),
           lifetime: Timer::from_seconds(1.25, TimerMode::Once),
         },
         movement: Movement::new(Vec3::new(0.00000001, 0., 0.), 1500.),
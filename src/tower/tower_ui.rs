@@ -57,6 +57,7 @@ pub struct UpgradeStats {
   pub path_index: usize,
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn update_tower_ui(
   assets: Res<GameAssets>,
   game_data: Res<GameData>,
@@ -113,8 +114,12 @@ fn update_tower_ui(
     for mut stats in stats_ui.iter_mut() {
       *stats = Text::from_section(
         format!(
-          " Damage: {}\n Attack Speed: {:.2}\n Range: {}\n Pierce: \n Projectile Speed: ",
-          tower.damage, tower.attack_speed, tower.range
+          " Element: {}\n Damage: {}\n Attack Speed: {:.2}\n Range: {}\n Camo Detection: {}\n Pierce: \n Projectile Speed: ",
+          tower_type.damage_type(),
+          tower.damage,
+          tower.attack_speed,
+          tower.range,
+          if tower.detection { "Yes" } else { "No" }
         ),
         stats.sections[0].style.clone(),
       );
@@ -180,6 +185,7 @@ fn update_tower_ui(
             TowerStat::Damage => upgrade_stats_string += &format!("+ {} damage\n", v),
             TowerStat::AttackSpeed => upgrade_stats_string += &format!("- {}% attack speed\n", v),
             TowerStat::Range => upgrade_stats_string += &format!("+ {} range\n", v),
+            TowerStat::Detection => upgrade_stats_string += "+ camo detection\n",
           }
         }
 
@@ -36,4 +36,6 @@ pub enum TowerStat {
   Damage,
   AttackSpeed,
   Range,
+  // Any value other than 0 lets the tower target camo enemies
+  Detection,
 }